@@ -0,0 +1,278 @@
+//! When the device was last touched, from real input.
+//!
+//! Taps on our own overlays are only a fraction of the input: most touches go
+//! to the RDP window. `InputMonitor` watches all of it, preferring the
+//! compositor's `ext-idle-notify-v1` (asking for notifications with a short
+//! timeout, so "idled" means "no input for `IDLE_NOTIFY_TIMEOUT`") and
+//! falling back to reading the evdev touch/keyboard/pointer devices directly,
+//! which needs read access to `/dev/input` (the `input` group). Input after
+//! a pause is also sent to the main loop straight away, so a blank screen
+//! can wake without waiting for the idle timer's next tick.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{delegate_noop, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notification_v1::{self, ExtIdleNotificationV1};
+use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notifier_v1::ExtIdleNotifierV1;
+
+use crate::log;
+
+pub const DEV_INPUT: &str = "/dev/input";
+pub const SYS_CLASS_INPUT: &str = "/sys/class/input";
+const IDLE_NOTIFY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Event type bits in capabilities/ev
+const EV_KEY: u64 = 1 << 0x01;
+const EV_REL: u64 = 1 << 0x02;
+const EV_ABS: u64 = 1 << 0x03;
+
+/// Where input activity comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    IdleNotify,
+    Evdev,
+}
+
+#[derive(Debug)]
+enum InputState {
+    /// Idle notifications: input resumed and has not idled again yet.
+    Ongoing,
+    /// Last input seen at this instant.
+    LastAt(Instant),
+}
+
+pub struct InputMonitor {
+    state: Arc<Mutex<InputState>>,
+    pub source: Source,
+}
+
+impl InputMonitor {
+    /// Watch input through ext-idle-notify, else evdev, calling `resumed`
+    /// (on an input thread) when input comes after at least
+    /// `IDLE_NOTIFY_TIMEOUT` without. `None` when neither is available.
+    pub fn start<F: Fn() + Clone + Send + 'static>(resumed: F) -> Option<InputMonitor> {
+        let state = Arc::new(Mutex::new(InputState::LastAt(Instant::now())));
+        match start_idle_notify(state.clone(), Box::new(resumed.clone())) {
+            Ok(()) => return Some(InputMonitor { state, source: Source::IdleNotify }),
+            Err(e) => log(&format!("ext-idle-notify unavailable: {}", e)),
+        }
+        let devices = input_devices(Path::new(DEV_INPUT), Path::new(SYS_CLASS_INPUT));
+        match watch_evdev(&devices, state.clone(), resumed) {
+            0 => {
+                log("No readable input devices - idle tracking limited to overlay taps");
+                None
+            }
+            n => {
+                log(&format!("Watching {} evdev input device(s) for activity", n));
+                Some(InputMonitor { state, source: Source::Evdev })
+            }
+        }
+    }
+
+    /// Time of the most recent input (now while input keeps coming).
+    pub fn last_input(&self) -> Instant {
+        match *self.state.lock().unwrap_or_else(|e| e.into_inner()) {
+            InputState::Ongoing => Instant::now(),
+            InputState::LastAt(at) => at,
+        }
+    }
+}
+
+fn set_state(state: &Mutex<InputState>, new: InputState) {
+    *state.lock().unwrap_or_else(|e| e.into_inner()) = new;
+}
+
+/// Record input seen now, calling `resumed` when it ends a pause.
+fn input_seen(state: &Mutex<InputState>, resumed: &impl Fn()) {
+    let now = Instant::now();
+    let previous = std::mem::replace(&mut *state.lock().unwrap_or_else(|e| e.into_inner()), InputState::LastAt(now));
+    if matches!(previous, InputState::LastAt(at) if now.duration_since(at) >= IDLE_NOTIFY_TIMEOUT) {
+        resumed();
+    }
+}
+
+struct NotifyState {
+    shared: Arc<Mutex<InputState>>,
+    resumed: Box<dyn Fn() + Send>,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for NotifyState {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, ()> for NotifyState {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => {
+                set_state(&state.shared, InputState::LastAt(Instant::now() - IDLE_NOTIFY_TIMEOUT));
+            }
+            ext_idle_notification_v1::Event::Resumed => {
+                set_state(&state.shared, InputState::Ongoing);
+                (state.resumed)();
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(NotifyState: ignore WlSeat);
+delegate_noop!(NotifyState: ExtIdleNotifierV1);
+
+/// Subscribe to idle notifications and dispatch them on a thread of their own.
+fn start_idle_notify(shared: Arc<Mutex<InputState>>, resumed: Box<dyn Fn() + Send>) -> Result<(), String> {
+    let conn = Connection::connect_to_env().map_err(|e| format!("no Wayland display: {}", e))?;
+    let (globals, mut queue) = registry_queue_init::<NotifyState>(&conn).map_err(|e| e.to_string())?;
+    let qh = queue.handle();
+    let notifier: ExtIdleNotifierV1 = globals
+        .bind(&qh, 1..=2, ())
+        .map_err(|_| "compositor does not offer ext_idle_notifier_v1".to_string())?;
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ()).map_err(|_| "no wl_seat".to_string())?;
+    let timeout = IDLE_NOTIFY_TIMEOUT.as_millis() as u32;
+    // v2 ignores idle inhibitors: sdl-freerdp3 inhibits idle while running,
+    // but we want to know about input, not about inhibitors
+    let notification = if notifier.version() >= 2 {
+        notifier.get_input_idle_notification(timeout, &seat, &qh, ())
+    } else {
+        notifier.get_idle_notification(timeout, &seat, &qh, ())
+    };
+    let mut state = NotifyState { shared, resumed };
+    queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+    std::thread::spawn(move || {
+        let _keep = (notifier, seat, notification);
+        loop {
+            if let Err(e) = queue.blocking_dispatch(&mut state) {
+                log(&format!("ext-idle-notify connection lost: {}", e));
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Whether a device's `capabilities/ev` bitmap (hex) makes it a human input
+/// device: keys/buttons, relative or absolute axes (touchscreens).
+pub fn is_human_input(ev_caps: &str) -> bool {
+    let bits = ev_caps.split_whitespace().last().and_then(|w| u64::from_str_radix(w, 16).ok()).unwrap_or(0);
+    bits & (EV_KEY | EV_REL | EV_ABS) != 0
+}
+
+/// `eventN` nodes under `dev_input` whose sysfs entry under `sys_input`
+/// describes a human input device.
+pub fn input_devices(dev_input: &Path, sys_input: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dev_input) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("event"))
+        .filter(|e| {
+            let caps = sys_input.join(e.file_name()).join("device/capabilities/ev");
+            std::fs::read_to_string(caps).is_ok_and(|c| is_human_input(&c))
+        })
+        .map(|e| e.path())
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// Reader thread per device; every read counts as input. Returns how many
+/// devices could be opened.
+fn watch_evdev(
+    devices: &[PathBuf],
+    state: Arc<Mutex<InputState>>,
+    resumed: impl Fn() + Clone + Send + 'static,
+) -> usize {
+    let mut watching = 0;
+    for path in devices {
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => {
+                log(&format!("Cannot read {}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let state = state.clone();
+        let resumed = resumed.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = file.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                input_seen(&state, &resumed);
+            }
+        });
+        watching += 1;
+    }
+    watching
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn picks_human_input_devices_and_sees_their_events() {
+        assert!(is_human_input("b\n"), "touchscreen: SYN KEY ABS");
+        assert!(is_human_input("120013"), "keyboard: SYN KEY MSC LED REP");
+        assert!(!is_human_input("21"), "SYN + SW only (lid switch)");
+        assert!(!is_human_input(""));
+
+        let root = std::env::temp_dir().join(format!("mote-input-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (dev, sys) = (root.join("dev"), root.join("sys"));
+        for (name, caps) in [("event0", "b"), ("event1", "21"), ("mice", "7")] {
+            std::fs::create_dir_all(sys.join(name).join("device/capabilities")).unwrap();
+            std::fs::write(sys.join(name).join("device/capabilities/ev"), caps).unwrap();
+            std::fs::create_dir_all(&dev).unwrap();
+            std::fs::write(dev.join(name), [0u8; 24]).unwrap();
+        }
+        let devices = input_devices(&dev, &sys);
+        assert_eq!(devices, [dev.join("event0")]);
+
+        let long_ago = Instant::now() - Duration::from_secs(600);
+        let state = Arc::new(Mutex::new(InputState::LastAt(long_ago)));
+        let monitor = InputMonitor { state: state.clone(), source: Source::Evdev };
+        assert_eq!(monitor.last_input(), long_ago);
+        let resumes = Arc::new(AtomicUsize::new(0));
+        let counter = resumes.clone();
+        let resumed = move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        };
+        assert_eq!(watch_evdev(&devices, state.clone(), resumed.clone()), 1);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while resumes.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(monitor.last_input().elapsed() < Duration::from_secs(5), "event not seen");
+        assert_eq!(resumes.load(Ordering::SeqCst), 1, "input after a pause must be reported");
+
+        // More input straight after is not a resume
+        input_seen(&state, &resumed);
+        assert_eq!(resumes.load(Ordering::SeqCst), 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,192 @@
+//! Auto-brightness from an IIO ambient light sensor.
+//!
+//! Reads `in_illuminance_raw` (with the channel's `_scale`/`_offset`) or
+//! `in_illuminance_input` under `/sys/bus/iio/devices/<device>`, smooths the
+//! readings, maps lux to a backlight percentage through a curve and only
+//! writes a new level once it differs enough from the last one it wrote. So
+//! a level picked on the slider stays until the room light really changes.
+//! Levels written here are not remembered: coming back from dimming or a
+//! restart, the backlight returns to the level saved with the slider.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backlight::Backlight;
+use crate::log;
+use crate::settings::AutoBrightnessSettings;
+
+pub const IIO_ROOT: &str = "/sys/bus/iio/devices";
+
+pub struct LightSensor {
+    dir: PathBuf,
+}
+
+impl LightSensor {
+    /// Device `name` under `root`, else the first with an illuminance channel.
+    pub fn open(root: &Path, name: Option<&str>) -> Option<LightSensor> {
+        let has_channel =
+            |dir: &Path| dir.join("in_illuminance_raw").exists() || dir.join("in_illuminance_input").exists();
+        let dir = match name {
+            Some(n) => Some(root.join(n)).filter(|d| has_channel(d)),
+            None => {
+                let mut dirs: Vec<PathBuf> = std::fs::read_dir(root)
+                    .ok()?
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|d| has_channel(d))
+                    .collect();
+                dirs.sort();
+                dirs.into_iter().next()
+            }
+        };
+        if dir.is_none() {
+            log(&format!("No ambient light sensor {} in {}", name.unwrap_or("found"), root.display()));
+        }
+        dir.map(|dir| LightSensor { dir })
+    }
+
+    /// Current illuminance in lux.
+    pub fn lux(&self) -> io::Result<f64> {
+        if let Ok(lux) = read_f64(&self.dir.join("in_illuminance_input")) {
+            return Ok(lux);
+        }
+        let raw = read_f64(&self.dir.join("in_illuminance_raw"))?;
+        let offset = read_f64(&self.dir.join("in_illuminance_offset")).unwrap_or(0.0);
+        let scale = read_f64(&self.dir.join("in_illuminance_scale")).unwrap_or(1.0);
+        Ok((raw + offset) * scale)
+    }
+}
+
+fn read_f64(path: &Path) -> io::Result<f64> {
+    std::fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// Lux to percent by linear interpolation between `(lux, percent)` points,
+/// flat beyond the first and last.
+pub fn curve_percent(curve: &[(f64, u32)], lux: f64) -> Option<u32> {
+    let (first, last) = (curve.first()?, curve.last()?);
+    if lux <= first.0 {
+        return Some(first.1);
+    }
+    if lux >= last.0 {
+        return Some(last.1);
+    }
+    let i = curve.windows(2).position(|w| lux < w[1].0)?;
+    let ((x0, y0), (x1, y1)) = (curve[i], curve[i + 1]);
+    let t = (lux - x0) / (x1 - x0);
+    Some((y0 as f64 + t * (y1 as f64 - y0 as f64)).round() as u32)
+}
+
+pub struct AutoBrightness {
+    sensor: LightSensor,
+    curve: Vec<(f64, u32)>,
+    hysteresis: u32,
+    smoothing: f64,
+    smoothed: Option<f64>,
+    applied: Option<u32>,
+}
+
+impl AutoBrightness {
+    /// From settings, with the sensor looked up under `iio_root`. `None`
+    /// when disabled or there is no sensor.
+    pub fn new(s: &AutoBrightnessSettings, iio_root: &Path) -> Option<AutoBrightness> {
+        if !s.enabled {
+            return None;
+        }
+        let mut curve: Vec<(f64, u32)> = s.curve.iter().map(|&(lux, p)| (lux, p.min(100))).collect();
+        curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if curve.is_empty() {
+            log("Auto-brightness curve is empty - auto-brightness off");
+            return None;
+        }
+        Some(AutoBrightness {
+            sensor: LightSensor::open(iio_root, s.sensor.as_deref())?,
+            curve,
+            hysteresis: s.hysteresis_percent,
+            smoothing: s.smoothing.clamp(0.01, 1.0),
+            smoothed: None,
+            applied: None,
+        })
+    }
+
+    /// Take a reading and update `backlight` when the target moved by at
+    /// least the hysteresis. Returns the percent written, if any.
+    pub fn update(&mut self, backlight: &Backlight) -> io::Result<Option<u32>> {
+        let lux = self.sensor.lux()?;
+        // Exponential moving average, so a passing shadow does not flicker
+        let smoothed = match self.smoothed {
+            Some(prev) => prev + self.smoothing * (lux - prev),
+            None => lux,
+        };
+        self.smoothed = Some(smoothed);
+        let Some(target) = curve_percent(&self.curve, smoothed) else {
+            return Ok(None);
+        };
+        if self.applied.is_some_and(|a| a.abs_diff(target) < self.hysteresis.max(1)) {
+            return Ok(None);
+        }
+        backlight.set_percent(target)?;
+        self.applied = Some(target);
+        Ok(Some(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_light_with_smoothing_and_hysteresis() {
+        let curve = [(0.0, 10), (100.0, 50), (500.0, 100)];
+        assert_eq!(curve_percent(&curve, -1.0), Some(10));
+        assert_eq!(curve_percent(&curve, 50.0), Some(30));
+        assert_eq!(curve_percent(&curve, 300.0), Some(75));
+        assert_eq!(curve_percent(&curve, 9000.0), Some(100));
+        assert_eq!(curve_percent(&[], 1.0), None);
+
+        let root = std::env::temp_dir().join(format!("mote-ambient-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (iio, bl_root) = (root.join("iio"), root.join("backlight"));
+        std::fs::create_dir_all(iio.join("iio:device0")).unwrap();
+        std::fs::create_dir_all(iio.join("iio:device1")).unwrap();
+        let raw = iio.join("iio:device1/in_illuminance_raw");
+        std::fs::write(&raw, "50\n").unwrap();
+        std::fs::write(iio.join("iio:device1/in_illuminance_scale"), "2.0\n").unwrap();
+        std::fs::create_dir_all(bl_root.join("panel")).unwrap();
+        std::fs::write(bl_root.join("panel/brightness"), "255").unwrap();
+        std::fs::write(bl_root.join("panel/max_brightness"), "255").unwrap();
+        let backlight = Backlight::open(&bl_root, None, root.join("state.toml")).unwrap();
+        // Saved with the slider
+        backlight.remember(200).unwrap();
+
+        let settings = AutoBrightnessSettings {
+            enabled: true,
+            curve: curve.to_vec(),
+            hysteresis_percent: 5,
+            smoothing: 0.6,
+            ..AutoBrightnessSettings::default()
+        };
+        assert!(AutoBrightness::new(&AutoBrightnessSettings { enabled: false, ..settings.clone() }, &iio).is_none());
+        let mut auto = AutoBrightness::new(&settings, &iio).expect("sensor with an illuminance channel");
+        assert_eq!(auto.sensor.lux().unwrap(), 100.0, "raw * scale");
+
+        assert_eq!(auto.update(&backlight).unwrap(), Some(50));
+        assert_eq!(backlight.percent().unwrap(), 50);
+        assert_eq!(backlight.remembered(), Some(200), "the slider's level must stay saved");
+
+        // 110 lux: smoothed 106, 51% - inside the hysteresis
+        std::fs::write(&raw, "55").unwrap();
+        assert_eq!(auto.update(&backlight).unwrap(), None);
+        // A dark room pulls the average down step by step
+        std::fs::write(&raw, "0").unwrap();
+        assert_eq!(auto.update(&backlight).unwrap(), Some(27), "106 -> 42.4 lux");
+        assert_eq!(auto.update(&backlight).unwrap(), Some(17), "42.4 -> 17 lux");
+        assert_eq!(backlight.percent().unwrap(), 17);
+        assert_eq!(backlight.remembered(), Some(200));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
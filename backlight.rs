@@ -0,0 +1,225 @@
+//! Panel backlight through `/sys/class/backlight/<device>`.
+//!
+//! Brightness is scaled to the device's `max_brightness` (not every panel
+//! uses 255). Turning off uses `bl_power` where the driver has it, so the
+//! brightness value survives, and otherwise writes 0 after remembering the
+//! level in `~/.config/mote/backlight.toml`. Turning on restores that level.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::log;
+
+pub const SYSFS_ROOT: &str = "/sys/class/backlight";
+/// `bl_power` values (FB_BLANK_UNBLANK / FB_BLANK_POWERDOWN).
+const BL_POWER_ON: &str = "0";
+const BL_POWER_OFF: &str = "4";
+
+pub struct Backlight {
+    name: String,
+    dir: PathBuf,
+    state_file: PathBuf,
+}
+
+impl Backlight {
+    /// The device named by `MOTE_BACKLIGHT`, or the first one found.
+    pub fn find() -> Option<Backlight> {
+        let name = std::env::var("MOTE_BACKLIGHT").ok();
+        Self::open(Path::new(SYSFS_ROOT), name.as_deref(), config::config_dir().join("backlight.toml"))
+    }
+
+    /// Device `name` under `root` (first in name order when `None`),
+    /// remembering levels in `state_file`.
+    pub fn open(root: &Path, name: Option<&str>, state_file: PathBuf) -> Option<Backlight> {
+        let name = match name {
+            Some(n) => n.to_string(),
+            None => {
+                let mut names: Vec<String> = std::fs::read_dir(root)
+                    .ok()?
+                    .flatten()
+                    .filter(|e| e.path().join("brightness").exists())
+                    .map(|e| e.file_name().to_string_lossy().into_owned())
+                    .collect();
+                names.sort();
+                names.into_iter().next()?
+            }
+        };
+        let dir = root.join(&name);
+        if !dir.join("brightness").exists() {
+            log(&format!("No backlight device {} in {}", name, root.display()));
+            return None;
+        }
+        Some(Backlight { name, dir, state_file })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn max(&self) -> io::Result<u32> {
+        read_number(&self.dir.join("max_brightness"))
+    }
+
+    pub fn level(&self) -> io::Result<u32> {
+        read_number(&self.dir.join("brightness"))
+    }
+
+    /// Set the raw brightness, clamped to `max_brightness`.
+    pub fn set_level(&self, level: u32) -> io::Result<()> {
+        let level = level.min(self.max()?);
+        std::fs::write(self.dir.join("brightness"), level.to_string())
+    }
+
+    /// Brightness in percent of `max_brightness`.
+    pub fn percent(&self) -> io::Result<u32> {
+        let max = self.max()?.max(1);
+        Ok(((self.level()? as f64 / max as f64) * 100.0).round() as u32)
+    }
+
+    pub fn set_percent(&self, percent: u32) -> io::Result<()> {
+        let max = self.max()?;
+        self.set_level(((percent.min(100) as f64 / 100.0) * max as f64).round() as u32)
+    }
+
+    /// Dim to `percent` unless the panel is already darker.
+    pub fn dim(&self, percent: u32) -> io::Result<()> {
+        let level = self.level()?;
+        let target = ((percent.min(100) as f64 / 100.0) * self.max()? as f64).round() as u32;
+        if level <= target {
+            return Ok(());
+        }
+        self.keep_level(level)?;
+        self.set_level(target)
+    }
+
+    pub fn off(&self) -> io::Result<()> {
+        self.keep_level(self.level()?)?;
+        let bl_power = self.dir.join("bl_power");
+        if bl_power.exists() {
+            std::fs::write(bl_power, BL_POWER_OFF)
+        } else {
+            std::fs::write(self.dir.join("brightness"), "0")
+        }
+    }
+
+    /// Power up and restore the remembered level (full brightness when
+    /// nothing was remembered yet).
+    pub fn on(&self) -> io::Result<()> {
+        let bl_power = self.dir.join("bl_power");
+        if bl_power.exists() {
+            std::fs::write(bl_power, BL_POWER_ON)?;
+        }
+        let level = match self.remembered() {
+            Some(l) if l > 0 => l,
+            _ => self.max()?,
+        };
+        if self.level()? != level {
+            self.set_level(level)?;
+        }
+        Ok(())
+    }
+
+    /// Level to restore on `on`: the one the user picked (see `remember`),
+    /// else the level found before the first dim or off.
+    pub fn remembered(&self) -> Option<u32> {
+        load_levels(&self.state_file).get(&self.name).copied()
+    }
+
+    /// Remember `level` unless a level is remembered already, so dimming and
+    /// blanking never overwrite the user's choice with a darker one.
+    fn keep_level(&self, level: u32) -> io::Result<()> {
+        if level > 0 && self.remembered().is_none() {
+            self.remember(level)?;
+        }
+        Ok(())
+    }
+
+    pub fn remember(&self, level: u32) -> io::Result<()> {
+        let mut levels = load_levels(&self.state_file);
+        levels.insert(self.name.clone(), level);
+        if let Some(dir) = self.state_file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(&levels).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(&self.state_file, text)
+    }
+}
+
+fn read_number(path: &Path) -> io::Result<u32> {
+    std::fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// Remembered levels by device name.
+fn load_levels(path: &Path) -> BTreeMap<String, u32> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| toml::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_device(root: &Path, name: &str, level: u32, max: u32, bl_power: bool) {
+        let dir = root.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("brightness"), format!("{}\n", level)).unwrap();
+        std::fs::write(dir.join("max_brightness"), format!("{}\n", max)).unwrap();
+        if bl_power {
+            std::fs::write(dir.join("bl_power"), "0\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn off_and_on_restore_level_within_max() {
+        let root = std::env::temp_dir().join(format!("mote-backlight-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        fake_device(&root, "rpi_backlight", 31, 31, true);
+        fake_device(&root, "10-0045", 700, 1023, false);
+        let state = root.join("state.toml");
+
+        // First in name order, or by name
+        let first = Backlight::open(&root, None, state.clone()).expect("first device");
+        assert_eq!(first.name(), "10-0045");
+        assert!(Backlight::open(&root, Some("missing"), state.clone()).is_none());
+        let rpi = Backlight::open(&root, Some("rpi_backlight"), state.clone()).expect("rpi_backlight");
+
+        rpi.set_level(255).unwrap();
+        assert_eq!(rpi.level().unwrap(), 31, "must clamp to max_brightness");
+        rpi.set_percent(50).unwrap();
+        assert_eq!(rpi.level().unwrap(), 16);
+        assert_eq!(rpi.percent().unwrap(), 52);
+
+        // bl_power device: dim, then power off keeping the level
+        let bl_power = || std::fs::read_to_string(root.join("rpi_backlight/bl_power")).unwrap();
+        rpi.dim(10).unwrap();
+        assert_eq!(rpi.level().unwrap(), 3);
+        assert_eq!(rpi.remembered(), Some(16));
+        rpi.off().unwrap();
+        assert_eq!(bl_power(), BL_POWER_OFF);
+        assert_eq!(rpi.remembered(), Some(16), "blanking while dimmed must keep the undimmed level");
+        assert_eq!(rpi.level().unwrap(), 3);
+        rpi.on().unwrap();
+        assert_eq!(bl_power(), BL_POWER_ON);
+        assert_eq!(rpi.level().unwrap(), 16);
+        rpi.set_level(8).unwrap();
+        rpi.dim(50).unwrap();
+        assert_eq!(rpi.level().unwrap(), 8, "already darker than the dim level");
+
+        // No bl_power: brightness 0, then the remembered level comes back
+        first.off().unwrap();
+        assert_eq!(first.level().unwrap(), 0);
+        let reopened = Backlight::open(&root, Some("10-0045"), state.clone()).unwrap();
+        assert_eq!(reopened.remembered(), Some(700));
+        reopened.on().unwrap();
+        assert_eq!(reopened.level().unwrap(), 700);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
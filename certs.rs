@@ -0,0 +1,339 @@
+//! Trust-on-first-use pinning of RDP server certificates.
+//!
+//! Before connecting, the server's TLS certificate is fetched with a short
+//! RDP negotiation + TLS handshake of our own and its SHA-256 fingerprint
+//! compared with the one accepted earlier for the profile (kept in
+//! `~/.config/mote/known_hosts.toml`). The pinned fingerprint is then passed
+//! to FreeRDP as `/cert:deny,fingerprint:sha256:<hex>`, so FreeRDP itself
+//! refuses any other certificate.
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config;
+use crate::log;
+use crate::profiles::Profile;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result of comparing the live certificate with the pinned one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    Trusted(String),
+    /// No fingerprint pinned yet for this profile.
+    New(String),
+    Changed { pinned: String, actual: String },
+}
+
+/// Fetch the server certificate for `profile` and compare it with its pin.
+pub fn check(profile: &Profile) -> Result<Check, String> {
+    let actual = probe_fingerprint(&profile.host, profile.port)?;
+    let pinned = KnownHosts::load().get(&profile.name).map(str::to_string);
+    Ok(compare(pinned, actual))
+}
+
+fn compare(pinned: Option<String>, actual: String) -> Check {
+    match pinned {
+        None => Check::New(actual),
+        Some(p) if p.eq_ignore_ascii_case(&actual) => Check::Trusted(actual),
+        Some(p) => Check::Changed { pinned: p, actual },
+    }
+}
+
+/// Remember `fingerprint` as the trusted certificate for `profile`.
+pub fn pin(profile: &Profile, fingerprint: &str) -> std::io::Result<()> {
+    let mut known = KnownHosts::load();
+    known.set(&profile.name, fingerprint);
+    known.save()
+}
+
+/// FreeRDP `/cert:` option for a pinned fingerprint (`None` denies every
+/// certificate, since nothing has been accepted yet).
+pub fn freerdp_cert_arg(fingerprint: Option<&str>) -> String {
+    match fingerprint {
+        // FreeRDP compares lowercase hex without separators
+        Some(fp) => format!("/cert:deny,fingerprint:sha256:{}", fp.to_ascii_lowercase()),
+        None => "/cert:deny".to_string(),
+    }
+}
+
+/// `ab12cd...` -> `AB:12:CD:...` for display.
+pub fn format_fingerprint(fp: &str) -> String {
+    fp.as_bytes()
+        .chunks(2)
+        .map(|c| String::from_utf8_lossy(c).to_ascii_uppercase())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Accepted fingerprints by profile name.
+pub struct KnownHosts {
+    path: PathBuf,
+    hosts: BTreeMap<String, String>,
+}
+
+impl KnownHosts {
+    pub fn path() -> PathBuf {
+        config::config_dir().join("known_hosts.toml")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        let hosts = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| match toml::from_str(&text) {
+                Ok(h) => Some(h),
+                Err(e) => {
+                    log(&format!("Invalid {}: {}", path.display(), e));
+                    None
+                }
+            })
+            .unwrap_or_default();
+        KnownHosts { path: path.to_path_buf(), hosts }
+    }
+
+    pub fn get(&self, profile: &str) -> Option<&str> {
+        self.hosts.get(profile).map(String::as_str)
+    }
+
+    pub fn set(&mut self, profile: &str, fingerprint: &str) {
+        self.hosts.insert(profile.to_string(), fingerprint.to_ascii_lowercase());
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(&self.hosts)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        // Write a 0600 temp file and rename, so pulling the plug mid-write
+        // never loses the pins
+        let tmp = self.path.with_extension("tmp");
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)?;
+        f.write_all(text.as_bytes())?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+/// SHA-256 (lowercase hex) of the certificate `host:port` presents for RDP.
+pub fn probe_fingerprint(host: &str, port: u16) -> Result<String, String> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve {}", host))?;
+    let mut tcp = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT)
+        .map_err(|e| format!("Could not connect to {}:{}: {}", host, port, e))?;
+    tcp.set_read_timeout(Some(PROBE_TIMEOUT)).ok();
+    tcp.set_write_timeout(Some(PROBE_TIMEOUT)).ok();
+
+    negotiate_tls(&mut tcp).map_err(|e| format!("RDP negotiation with {} failed: {}", host, e))?;
+
+    let provider = Arc::new(crypto::ring::default_provider());
+    let tls_config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+    let mut tls = ClientConnection::new(Arc::new(tls_config), server_name).map_err(|e| e.to_string())?;
+    while tls.is_handshaking() {
+        tls.complete_io(&mut tcp)
+            .map_err(|e| format!("TLS handshake with {} failed: {}", host, e))?;
+    }
+    let cert = tls
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .ok_or_else(|| format!("{} sent no certificate", host))?;
+    Ok(hex(&Sha256::digest(cert.as_ref())))
+}
+
+/// X.224 Connection Request asking for TLS (with or without CredSSP), and
+/// check the server's Connection Confirm agreed.
+fn negotiate_tls(tcp: &mut TcpStream) -> std::io::Result<()> {
+    const PROTOCOL_SSL_OR_HYBRID: u8 = 0x01 | 0x02;
+    let request: [u8; 19] = [
+        0x03, 0x00, 0x00, 19, // TPKT header, total length 19
+        0x0e, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00, // X.224 CR TPDU
+        0x01, 0x00, 0x08, 0x00, PROTOCOL_SSL_OR_HYBRID, 0x00, 0x00, 0x00, // RDP_NEG_REQ
+    ];
+    tcp.write_all(&request)?;
+
+    let mut header = [0u8; 4];
+    tcp.read_exact(&mut header)?;
+    let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if header[0] != 0x03 || !(11..=512).contains(&len) {
+        return Err(invalid("not an RDP server"));
+    }
+    let mut body = vec![0u8; len - 4];
+    tcp.read_exact(&mut body)?;
+    // body: LI, CC code, dst-ref(2), src-ref(2), class, then RDP_NEG_RSP/FAILURE
+    if body.len() < 15 || body[1] & 0xf0 != 0xd0 {
+        return Err(invalid("server did not offer TLS"));
+    }
+    let neg = &body[7..15];
+    match neg[0] {
+        0x02 if neg[4] & PROTOCOL_SSL_OR_HYBRID != 0 => Ok(()),
+        0x03 => Err(invalid(&format!("server refused TLS (failure code {})", neg[4]))),
+        _ => Err(invalid("server only supports legacy RDP security")),
+    }
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Verifier for the probe only: accepts any certificate (the fingerprint is
+/// what we check) but still verifies handshake signatures.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_fingerprint_detects_change() {
+        let fp = "ab".repeat(32);
+        assert_eq!(compare(None, fp.clone()), Check::New(fp.clone()));
+        assert_eq!(compare(Some(fp.to_uppercase()), fp.clone()), Check::Trusted(fp.clone()));
+        let other = "cd".repeat(32);
+        assert_eq!(
+            compare(Some(other.clone()), fp.clone()),
+            Check::Changed { pinned: other, actual: fp.clone() }
+        );
+        assert_eq!(
+            freerdp_cert_arg(Some(&fp.to_uppercase())),
+            format!("/cert:deny,fingerprint:sha256:{}", fp)
+        );
+        assert!(format_fingerprint(&fp).starts_with("AB:AB:"), "{}", format_fingerprint(&fp));
+    }
+
+    #[test]
+    fn known_hosts_are_saved_privately_in_one_piece() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("mote-known-hosts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("known_hosts.toml");
+
+        let mut known = KnownHosts::load_from(&path);
+        known.set("Living room", &"AB".repeat(32));
+        known.save().expect("save");
+        let mode = std::fs::metadata(&path).expect("saved").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "known hosts readable by others: {:o}", mode);
+        assert!(!path.with_extension("tmp").exists(), "temp file left behind");
+        assert_eq!(KnownHosts::load_from(&path).get("Living room"), Some("ab".repeat(32).as_str()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// One-shot server on a local port: reads the X.224 request, answers
+    /// with `reply` and hangs up. Joins to the request it received.
+    fn fake_server(reply: Vec<u8>) -> (u16, std::thread::JoinHandle<Vec<u8>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut request = [0u8; 19];
+            conn.read_exact(&mut request).unwrap();
+            conn.write_all(&reply).unwrap();
+            request.to_vec()
+        });
+        (port, server)
+    }
+
+    /// X.224 Connection Confirm carrying an RDP_NEG_RSP (type 2) or
+    /// RDP_NEG_FAILURE (type 3) with `value`.
+    fn confirm(neg_type: u8, value: u8) -> Vec<u8> {
+        vec![
+            0x03, 0x00, 0x00, 19, 0x0e, 0xd0, 0x00, 0x00, 0x12, 0x34, 0x00, neg_type, 0x00, 0x08, 0x00, value, 0x00,
+            0x00, 0x00,
+        ]
+    }
+
+    #[test]
+    fn probe_negotiates_tls_with_the_server() {
+        // Server agrees to TLS, then hangs up instead of a handshake
+        let (port, server) = fake_server(confirm(0x02, 0x01));
+        let err = probe_fingerprint("127.0.0.1", port).unwrap_err();
+        let request = server.join().unwrap();
+        assert_eq!(&request[..4], &[0x03, 0x00, 0x00, 19], "TPKT header");
+        assert_eq!(request[5], 0xe0, "X.224 Connection Request");
+        assert_eq!((request[11], request[15]), (0x01, 0x03), "RDP_NEG_REQ for TLS or CredSSP");
+        assert!(err.contains("TLS handshake"), "negotiation should have passed: {}", err);
+
+        let (port, server) = fake_server(confirm(0x03, 0x05));
+        let err = probe_fingerprint("127.0.0.1", port).unwrap_err();
+        server.join().unwrap();
+        assert!(err.contains("refused TLS (failure code 5)"), "{}", err);
+
+        let (port, server) = fake_server(confirm(0x02, 0x00));
+        let err = probe_fingerprint("127.0.0.1", port).unwrap_err();
+        server.join().unwrap();
+        assert!(err.contains("legacy RDP security"), "{}", err);
+
+        let (port, server) = fake_server(b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec());
+        let err = probe_fingerprint("127.0.0.1", port).unwrap_err();
+        server.join().unwrap();
+        assert!(err.contains("not an RDP server"), "{}", err);
+    }
+}
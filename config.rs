@@ -0,0 +1,135 @@
+//! User configuration read from `~/.config/mote/` (or `$XDG_CONFIG_HOME/mote/`).
+//!
+//! Every file is optional: when it is missing or unreadable the built-in
+//! defaults are used, so a fresh Pi behaves exactly like the hardcoded build.
+
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::log;
+
+/// Placeholder in icon commands replaced with the path of this executable.
+pub const EXE_PLACEHOLDER: &str = "{exe}";
+
+/// Directory holding Mote's config files.
+pub fn config_dir() -> PathBuf {
+    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        if !dir.is_empty() {
+            return Path::new(&dir).join("mote");
+        }
+    }
+    let home = env::var("HOME").unwrap_or_else(|_| "/home/m".to_string());
+    Path::new(&home).join(".config").join("mote")
+}
+
+/// One home-screen icon as declared in `icons.toml`:
+///
+/// ```toml
+/// [[icon]]
+/// label = "Chromium"
+/// icon = "web-browser"
+/// command = "chromium --ozone-platform=wayland"
+/// order = 1
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IconConfig {
+    pub label: String,
+    /// Freedesktop icon name (e.g. "video-display").
+    pub icon: String,
+    /// Shell command run on tap; `{exe}` expands to this binary.
+    pub command: String,
+    /// Left-to-right position; ties keep file order.
+    #[serde(default)]
+    pub order: i32,
+}
+
+#[derive(Deserialize)]
+struct IconsFile {
+    #[serde(default, rename = "icon")]
+    icons: Vec<IconConfig>,
+}
+
+/// The icon set shipped before icons became configurable. Mote icons are not
+/// listed here: one is added per connection profile (see `profiles`).
+pub fn default_icons() -> Vec<IconConfig> {
+    let icon = |label: &str, icon: &str, command: &str, order: i32| IconConfig {
+        label: label.to_string(),
+        icon: icon.to_string(),
+        command: command.to_string(),
+        order,
+    };
+    vec![
+        icon("Chromium", "web-browser", "chromium --ozone-platform=wayland", 1),
+        icon("Shutdown", "system-shutdown", "sudo shutdown -h now", 2),
+        icon("Reboot", "system-reboot", "sudo reboot", 3),
+    ]
+}
+
+/// Parse the contents of an `icons.toml` file, sorted by `order`.
+pub fn parse_icons(text: &str) -> Result<Vec<IconConfig>, String> {
+    let file: IconsFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut icons = file.icons;
+    icons.sort_by_key(|i| i.order);
+    Ok(icons)
+}
+
+/// Load home-screen icons from `icons.toml`, falling back to `default_icons()`
+/// when the file is missing or invalid.
+pub fn load_icons() -> Vec<IconConfig> {
+    load_icons_from(&config_dir().join("icons.toml"))
+}
+
+pub fn load_icons_from(path: &Path) -> Vec<IconConfig> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(_) => {
+            log(&format!("No icon config at {}, using built-in icons", path.display()));
+            return default_icons();
+        }
+    };
+    match parse_icons(&text) {
+        Ok(icons) => {
+            log(&format!("Loaded {} icons from {}", icons.len(), path.display()));
+            icons
+        }
+        Err(e) => {
+            log(&format!("Invalid icon config {}: {} - using built-in icons", path.display(), e));
+            default_icons()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icons_parse_and_sort_by_order() {
+        let icons = parse_icons(
+            r#"
+            [[icon]]
+            label = "Reboot"
+            icon = "system-reboot"
+            command = "sudo reboot"
+            order = 5
+
+            [[icon]]
+            label = "Mote"
+            icon = "video-display"
+            command = "{exe} --mote-view"
+            "#,
+        )
+        .expect("valid icons.toml");
+        let labels: Vec<_> = icons.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, ["Mote", "Reboot"], "wrong order: {:?}", labels);
+        assert_eq!(icons[0].command, "{exe} --mote-view");
+    }
+
+    #[test]
+    fn missing_icons_file_falls_back_to_defaults() {
+        let icons = load_icons_from(Path::new("/nonexistent/mote/icons.toml"));
+        assert_eq!(icons, default_icons());
+        assert_eq!(icons.len(), 3, "expected Chromium, Shutdown, Reboot: {:?}", icons);
+    }
+}
@@ -0,0 +1,582 @@
+//! On-device credential store for RDP and SSH secrets.
+//!
+//! Profiles refer to secrets by ID (`credential = "living-room"`). Secrets are
+//! kept in the Secret Service (via `secret-tool`) when a session keyring is
+//! reachable, otherwise in `~/.config/mote/credentials.enc`, encrypted with
+//! ChaCha20-Poly1305 under a key derived from a random `credentials.key` and
+//! the Pi's SoC serial number.
+//!
+//! Threat model for the file store: both files are 0600, so other local
+//! users (the concern behind keeping passwords out of argv) can read neither.
+//! The key file is random rather than derived from `/etc/machine-id` or the
+//! serial, which every local user can read. The serial lives in the CPU, not
+//! on the SD card, so a copied card or a backup of `~/.config` alone does not
+//! decrypt the store. Root and the Mote user itself can always decrypt it;
+//! use the Secret Service where a locked keyring is wanted.
+//!
+//! A password from before the store (`MOTE_RDP_PASSWORD`) is moved into it on
+//! first use, with a warning in the log to remove it from the environment.
+//!
+//! Managed from the shell; secrets are read from stdin, never from argv:
+//!
+//! ```text
+//! desktop-icons credential add living-room [--kind rdp|ssh]
+//! desktop-icons credential rotate living-room
+//! desktop-icons credential delete living-room
+//! desktop-icons credential list
+//! ```
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config;
+use crate::log;
+
+const FILE_MAGIC: &[u8] = b"MOTECRED1";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+/// Environment variable that held the RDP password before the store.
+const LEGACY_PASSWORD_VAR: &str = "MOTE_RDP_PASSWORD";
+const SECRET_SERVICE: &str = "mote";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Rdp,
+    Ssh,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Rdp => "rdp",
+            Kind::Ssh => "ssh",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Kind> {
+        match s {
+            "rdp" => Some(Kind::Rdp),
+            "ssh" => Some(Kind::Ssh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    kind: Kind,
+    secret: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NotFound(String),
+    Io(io::Error),
+    Corrupt(String),
+    SecretTool(String),
+    /// Stored, but not as an RDP password (an SSH secret, say).
+    NotRdp(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(id) => write!(
+                f,
+                "No credential \"{}\" stored. Add it with: desktop-icons credential add {}",
+                id, id
+            ),
+            Error::Io(e) => write!(f, "Credential store I/O error: {}", e),
+            Error::Corrupt(msg) => write!(f, "Credential store unreadable: {}", msg),
+            Error::SecretTool(msg) => write!(f, "secret-tool failed: {}", msg),
+            Error::NotRdp(id) => write!(f, "Credential \"{}\" is not an RDP password", id),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Encrypted credentials file.
+pub struct FileStore {
+    path: PathBuf,
+    key: [u8; 32],
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf, key: [u8; 32]) -> Self {
+        FileStore { path, key }
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, Entry>, Error> {
+        let data = match std::fs::read(&self.path) {
+            Ok(d) => d,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < FILE_MAGIC.len() + NONCE_LEN || !data.starts_with(FILE_MAGIC) {
+            return Err(Error::Corrupt(format!("{} is not a Mote credential file", self.path.display())));
+        }
+        let (nonce, ciphertext) = data[FILE_MAGIC.len()..].split_at(NONCE_LEN);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let plain = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Corrupt("decryption failed (different device?)".to_string()))?;
+        let text = String::from_utf8(plain).map_err(|e| Error::Corrupt(e.to_string()))?;
+        toml::from_str(&text).map_err(|e| Error::Corrupt(e.to_string()))
+    }
+
+    fn save(&self, entries: &BTreeMap<String, Entry>) -> Result<(), Error> {
+        let text = toml::to_string(entries).map_err(|e| Error::Corrupt(e.to_string()))?;
+        let mut nonce = [0u8; NONCE_LEN];
+        std::fs::File::open("/dev/urandom")?.read_exact(&mut nonce)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), text.as_bytes())
+            .map_err(|_| Error::Corrupt("encryption failed".to_string()))?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write a 0600 temp file and rename so a crash never leaves half a store
+        let tmp = self.path.with_extension("tmp");
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)?;
+        f.write_all(FILE_MAGIC)?;
+        f.write_all(&nonce)?;
+        f.write_all(&ciphertext)?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+pub enum Store {
+    SecretService,
+    File(FileStore),
+}
+
+impl Store {
+    /// Secret Service when a keyring answers, the encrypted file otherwise.
+    /// `MOTE_CREDENTIAL_BACKEND=file|secret-service` forces one.
+    pub fn open() -> Result<Store, Error> {
+        let forced = env::var("MOTE_CREDENTIAL_BACKEND").unwrap_or_default();
+        let use_secret_service = match forced.as_str() {
+            "file" => false,
+            "secret-service" => true,
+            _ => secret_service_available(),
+        };
+        if use_secret_service {
+            return Ok(Store::SecretService);
+        }
+        let dir = config::config_dir();
+        let key = device_key(&load_or_create_key(&dir.join("credentials.key"))?);
+        Ok(Store::File(FileStore::new(dir.join("credentials.enc"), key)))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Store::SecretService => "Secret Service",
+            Store::File(_) => "encrypted file",
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Result<String, Error> {
+        match self {
+            Store::SecretService => secret_tool_lookup(&["id", id])?.ok_or_else(|| Error::NotFound(id.to_string())),
+            Store::File(fs) => fs
+                .load()?
+                .remove(id)
+                .map(|e| e.secret)
+                .ok_or_else(|| Error::NotFound(id.to_string())),
+        }
+    }
+
+    /// The secret for `id`, which has to be stored as `Kind::Rdp`.
+    pub fn get_rdp(&self, id: &str) -> Result<String, Error> {
+        match self {
+            Store::SecretService => match secret_tool_lookup(&["id", id, "kind", Kind::Rdp.as_str()])? {
+                Some(secret) => Ok(secret),
+                None => self.get(id).and_then(|_| Err(Error::NotRdp(id.to_string()))),
+            },
+            Store::File(fs) => match fs.load()?.remove(id) {
+                Some(Entry { kind: Kind::Rdp, secret }) => Ok(secret),
+                Some(_) => Err(Error::NotRdp(id.to_string())),
+                None => Err(Error::NotFound(id.to_string())),
+            },
+        }
+    }
+
+    /// Add or replace the secret for `id`.
+    pub fn put(&self, id: &str, kind: Kind, secret: &str) -> Result<(), Error> {
+        match self {
+            Store::SecretService => {
+                let mut child = Command::new("secret-tool")
+                    .args(["store", &format!("--label=Mote {} credential {}", kind.as_str(), id)])
+                    .args(["service", SECRET_SERVICE, "id", id, "kind", kind.as_str()])
+                    .stdin(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(secret.as_bytes())?;
+                }
+                let out = child.wait_with_output()?;
+                if !out.status.success() {
+                    return Err(Error::SecretTool(String::from_utf8_lossy(&out.stderr).trim().to_string()));
+                }
+                Ok(())
+            }
+            Store::File(fs) => {
+                let mut entries = fs.load()?;
+                entries.insert(id.to_string(), Entry { kind, secret: secret.to_string() });
+                fs.save(&entries)
+            }
+        }
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), Error> {
+        match self {
+            Store::SecretService => {
+                self.get(id)?;
+                let out = Command::new("secret-tool")
+                    .args(["clear", "service", SECRET_SERVICE, "id", id])
+                    .output()?;
+                if !out.status.success() {
+                    return Err(Error::SecretTool(String::from_utf8_lossy(&out.stderr).trim().to_string()));
+                }
+                Ok(())
+            }
+            Store::File(fs) => {
+                let mut entries = fs.load()?;
+                if entries.remove(id).is_none() {
+                    return Err(Error::NotFound(id.to_string()));
+                }
+                fs.save(&entries)
+            }
+        }
+    }
+
+    /// The RDP secret for `id`. When there is none, `legacy` (the name of the
+    /// environment variable it was found in, and its value) is saved under
+    /// `id` first, so deployments from before the store keep working.
+    pub fn get_or_import(&self, id: &str, legacy: Option<(&str, String)>) -> Result<String, Error> {
+        match (self.get_rdp(id), legacy) {
+            (Err(Error::NotFound(_)), Some((var, secret))) => {
+                match self.put(id, Kind::Rdp, &secret) {
+                    Ok(()) => log(&format!(
+                        "WARNING: moved the password from ${} into the {} as \"{}\" - remove {} from the environment",
+                        var,
+                        self.name(),
+                        id,
+                        var
+                    )),
+                    Err(e) => log(&format!("WARNING: using the password from ${}; storing it failed: {}", var, e)),
+                }
+                Ok(secret)
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Stored IDs and kinds (never the secrets).
+    pub fn list(&self) -> Result<Vec<(String, Kind)>, Error> {
+        match self {
+            Store::SecretService => {
+                let out = Command::new("secret-tool")
+                    .args(["search", "--all", "--unlock", "service", SECRET_SERVICE])
+                    .output()?;
+                // secret-tool prints "attribute.id = ..." lines per item, on
+                // stdout or stderr depending on version
+                let text = format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&out.stdout),
+                    String::from_utf8_lossy(&out.stderr)
+                );
+                let mut items = Vec::new();
+                let mut id: Option<String> = None;
+                let mut kind = Kind::Rdp;
+                for line in text.lines() {
+                    if line.starts_with('[') {
+                        if let Some(i) = id.take() {
+                            items.push((i, kind));
+                        }
+                        kind = Kind::Rdp;
+                    } else if let Some(v) = line.strip_prefix("attribute.id = ") {
+                        id = Some(v.to_string());
+                    } else if let Some(v) = line.strip_prefix("attribute.kind = ") {
+                        kind = Kind::parse(v).unwrap_or(Kind::Rdp);
+                    }
+                }
+                if let Some(i) = id {
+                    items.push((i, kind));
+                }
+                Ok(items)
+            }
+            Store::File(fs) => Ok(fs.load()?.into_iter().map(|(id, e)| (id, e.kind)).collect()),
+        }
+    }
+}
+
+/// Look up a secret by ID in the default store.
+#[allow(dead_code)] // Used by the volume buttons when re-enabled
+pub fn lookup(id: &str) -> Result<String, Error> {
+    Store::open()?.get(id)
+}
+
+/// Look up an RDP password by ID, importing it from `LEGACY_PASSWORD_VAR`
+/// when the store has none.
+pub fn lookup_rdp(id: &str) -> Result<String, Error> {
+    let legacy = env::var(LEGACY_PASSWORD_VAR).ok().filter(|p| !p.is_empty()).map(|p| (LEGACY_PASSWORD_VAR, p));
+    Store::open()?.get_or_import(id, legacy)
+}
+
+/// The secret `secret-tool` finds for `attributes` (besides the service).
+fn secret_tool_lookup(attributes: &[&str]) -> Result<Option<String>, Error> {
+    let out = Command::new("secret-tool").args(["lookup", "service", SECRET_SERVICE]).args(attributes).output()?;
+    if !out.status.success() || out.stdout.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&out.stdout).trim_end_matches('\n').to_string()))
+}
+
+fn secret_service_available() -> bool {
+    if env::var("DBUS_SESSION_BUS_ADDRESS").is_err() {
+        return false;
+    }
+    Command::new("secret-tool")
+        .args(["search", "service", SECRET_SERVICE])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Random key material from `path`, created 0600 on first use. Created
+/// under a temporary name and hard-linked into place, so the home screen and
+/// Mote view starting together end up with the same key.
+fn load_or_create_key(path: &Path) -> Result<[u8; KEY_LEN], Error> {
+    match std::fs::read(path) {
+        Ok(data) => {
+            return data
+                .try_into()
+                .map_err(|_| Error::Corrupt(format!("{} is not a {}-byte key", path.display(), KEY_LEN)))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let mut key = [0u8; KEY_LEN];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut key)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut f = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    f.write_all(&key)?;
+    f.sync_all()?;
+    let linked = std::fs::hard_link(&tmp, path);
+    std::fs::remove_file(&tmp).ok();
+    match linked {
+        Ok(()) => {
+            log(&format!("Created credential key {}", path.display()));
+            Ok(key)
+        }
+        // Someone else was first: use theirs
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => load_or_create_key(path),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Key for the credentials file: SHA-256 over the key file's contents and
+/// the SoC serial number (Pi).
+fn device_key(file_key: &[u8; KEY_LEN]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"mote-credentials-v2");
+    hasher.update(file_key);
+    for source in ["/sys/firmware/devicetree/base/serial-number", "/proc/device-tree/serial-number"] {
+        if let Ok(data) = std::fs::read(source) {
+            hasher.update(&data);
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Read a secret from stdin, without echo when stdin is a terminal.
+fn read_secret(prompt: &str) -> io::Result<String> {
+    let tty = Command::new("stty")
+        .arg("-echo")
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if tty {
+        eprint!("{}", prompt);
+    }
+    let mut line = String::new();
+    let res = io::stdin().lock().read_line(&mut line);
+    if tty {
+        Command::new("stty").arg("echo").stdin(Stdio::inherit()).status().ok();
+        eprintln!();
+    }
+    res?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+const USAGE: &str = "usage: desktop-icons credential <add ID [--kind rdp|ssh] | rotate ID | delete ID | list>";
+
+/// `desktop-icons credential ...`; returns the process exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let store = match Store::open() {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["add", id, rest @ ..] => {
+            let kind = match rest {
+                [] => Some(Kind::Rdp),
+                ["--kind", k] => Kind::parse(k),
+                _ => None,
+            };
+            let Some(kind) = kind else {
+                eprintln!("{}", USAGE);
+                return 2;
+            };
+            read_secret("Secret: ")
+                .map_err(Error::from)
+                .and_then(|secret| store.put(id, kind, &secret))
+        }
+        ["rotate", id] => {
+            // Keep the kind of the existing entry
+            let kind = match store.list() {
+                Ok(items) => match items.into_iter().find(|(i, _)| i == id) {
+                    Some((_, kind)) => kind,
+                    None => {
+                        eprintln!("{}", Error::NotFound(id.to_string()));
+                        return 1;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 1;
+                }
+            };
+            read_secret("New secret: ")
+                .map_err(Error::from)
+                .and_then(|secret| store.put(id, kind, &secret))
+        }
+        ["delete", id] => store.delete(id),
+        ["list"] => store.list().map(|items| {
+            for (id, kind) in items {
+                println!("{}\t{}", id, kind.as_str());
+            }
+        }),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    match result {
+        Ok(()) => {
+            if let [cmd, id, ..] = args.as_slice() {
+                log(&format!("credential {} {} ({})", cmd, id, store.name()));
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store_encrypts_and_round_trips() {
+        let dir = env::temp_dir().join(format!("mote-cred-test-{}", std::process::id()));
+        let path = dir.join("credentials.enc");
+        let store = Store::File(FileStore::new(path.clone(), [7u8; 32]));
+
+        store.put("living-room", Kind::Rdp, "hunter2-rdp").expect("put");
+        store.put("nas", Kind::Ssh, "ssh-pass").expect("put");
+        let raw = std::fs::read(&path).expect("store file written");
+        assert!(
+            !String::from_utf8_lossy(&raw).contains("hunter2"),
+            "secret stored in plaintext"
+        );
+        assert_eq!(store.get("living-room").expect("get"), "hunter2-rdp");
+        assert_eq!(store.get_rdp("living-room").expect("get_rdp"), "hunter2-rdp");
+        // An SSH secret never goes to the RDP client
+        assert!(matches!(store.get_rdp("nas"), Err(Error::NotRdp(_))));
+        assert!(matches!(store.get_rdp("office"), Err(Error::NotFound(_))));
+
+        store.delete("living-room").expect("delete");
+        assert!(matches!(store.get("living-room"), Err(Error::NotFound(_))));
+        assert_eq!(store.list().expect("list"), vec![("nas".to_string(), Kind::Ssh)]);
+
+        // A different device key must not decrypt the file
+        let other = Store::File(FileStore::new(path, [8u8; 32]));
+        assert!(matches!(other.get("nas"), Err(Error::Corrupt(_))));
+
+        // A password from the environment is moved in once, then comes
+        // from the store
+        let legacy = || Some((LEGACY_PASSWORD_VAR, "from-env".to_string()));
+        assert_eq!(store.get_or_import("living-room", legacy()).expect("import"), "from-env");
+        assert_eq!(store.get("living-room").expect("imported"), "from-env");
+        assert!(
+            matches!(store.get_or_import("nas", legacy()), Err(Error::NotRdp(_))),
+            "nothing is imported over an SSH secret"
+        );
+        assert_eq!(store.get("nas").expect("untouched"), "ssh-pass");
+        assert!(matches!(store.get_or_import("office", None), Err(Error::NotFound(_))));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn key_file_is_private_random_and_stable() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = env::temp_dir().join(format!("mote-cred-key-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("credentials.key");
+
+        let key = load_or_create_key(&path).expect("create key");
+        let mode = std::fs::metadata(&path).expect("key file").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "key file readable by others: {:o}", mode);
+        assert_eq!(load_or_create_key(&path).expect("reload key"), key);
+        assert_ne!(load_or_create_key(&dir.join("other.key")).expect("second key"), key);
+        assert_ne!(device_key(&key), device_key(&[0u8; KEY_LEN]));
+
+        std::fs::write(&path, b"short").unwrap();
+        assert!(matches!(load_or_create_key(&path), Err(Error::Corrupt(_))));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
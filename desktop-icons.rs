@@ -5,16 +5,68 @@ use gtk::gdk::{Screen, RGBA};
 use gtk_layer_shell::{Edge, Layer};
 use glib::{timeout_add_local, MainLoop, Continue};
 use gtk::gdk::EventMask;
-use std::process::{Command, Stdio};
-use std::io::Read;
+use std::process::Command;
 use std::env;
 use std::time::{Duration, Instant};
 use std::rc::Rc;
 use std::cell::{Cell, RefCell};
 use std::path::Path;
 
+mod activity;
+mod ambient;
+mod backlight;
+mod certs;
+mod config;
+mod credentials;
+mod failures;
+mod freerdp;
+mod idle;
+mod output_power;
+mod park;
+mod profiles;
+mod schedule;
+mod screensaver;
+mod session;
+mod settings;
+mod sway_ipc;
+
+use activity::InputMonitor;
+use ambient::AutoBrightness;
+use backlight::Backlight;
+use idle::{Action, IdleStages, IdleTimer, KeepAwake, Tick};
+use profiles::Profile;
+use schedule::Schedule;
+use settings::{ScreensaverMode, ScreensaverSettings, WakeMode};
+use session::{ReconnectPolicy, Supervisor};
+use sway_ipc::{Subscription, SwayEvent, WindowChange, WorkspaceChange};
+
 const MOTE_ACTIVE_FLAG: &str = "/tmp/mote-active";
 
+/// The running RDP session, if any; shared by the overlays that can end it.
+type SessionSlot = Rc<RefCell<Option<Supervisor>>>;
+/// Mote view controls, created once the RDP window is up.
+type OverlaySlot = Rc<RefCell<Option<MoteOverlay>>>;
+
+/// Sway workspace of the home screen, and the one parked sessions wait on.
+const HOME_WORKSPACE: &str = "1";
+const PARKED_WORKSPACE: &str = "mote-parked";
+
+/// How long Disconnect waits for the RDP window to close before leaving anyway.
+const WINDOW_CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the RDP window is looked up in the sway tree when window events
+/// are not available.
+const TREE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Home-screen icon layout: 72x72 icons, 16px from the left edge, 12px apart.
+const ICON_SIZE: i32 = 72;
+const ICON_MARGIN: i32 = 16;
+const ICON_SPACING: i32 = 12;
+const BRIGHTNESS_SLIDER_WIDTH: i32 = 200;
+const KEEP_AWAKE_WIDTH: i32 = 112;
+const DISCONNECT_WIDTH: i32 = 104;
+/// Lowest slider position, so the panel can't be dimmed to black.
+const MIN_BRIGHTNESS_PERCENT: f64 = 5.0;
+
 struct Icon {
     window: Window,
 }
@@ -36,6 +88,8 @@ struct MoteOverlay {
     trigger_window: Rc<Window>,
     #[allow(dead_code)] // Must keep windows alive  
     wake_overlay: Rc<Window>,
+    #[allow(dead_code)] // Must keep windows alive
+    keep_awake_badge: Rc<Window>,
 }
 
 impl Icon {
@@ -45,14 +99,14 @@ impl Icon {
         window.set_skip_taskbar_hint(true);
         window.set_keep_above(true);
         // Fixed small size like real desktop icons (icon + label)
-        window.set_default_size(72, 72);
-        window.set_size_request(72, 72);
+        window.set_default_size(ICON_SIZE, ICON_SIZE);
+        window.set_size_request(ICON_SIZE, ICON_SIZE);
 
         gtk_layer_shell::init_for_window(&window);
         gtk_layer_shell::set_layer(&window, Layer::Top);
         gtk_layer_shell::set_anchor(&window, Edge::Top, true);
         gtk_layer_shell::set_anchor(&window, Edge::Left, true);
-        gtk_layer_shell::set_margin(&window, Edge::Top, 16);
+        gtk_layer_shell::set_margin(&window, Edge::Top, ICON_MARGIN);
         gtk_layer_shell::set_margin(&window, Edge::Left, margin_left);
         gtk_layer_shell::auto_exclusive_zone_enable(&window);
 
@@ -91,13 +145,13 @@ impl Icon {
             );
         }
         let box_ = GtkBox::new(Orientation::Vertical, 2);
-        box_.set_size_request(72, 72);
+        box_.set_size_request(ICON_SIZE, ICON_SIZE);
 
         let icon = Image::from_icon_name(Some(icon_name), IconSize::Dialog);
         icon.set_pixel_size(32);
 
         let label_name = Label::new(None);
-        label_name.set_markup(&format!("<span font='Sans 9'>{}</span>", name));
+        label_name.set_markup(&format!("<span font='Sans 9'>{}</span>", glib::markup_escape_text(name)));
         label_name.set_ellipsize(gtk::pango::EllipsizeMode::End);
         label_name.set_max_width_chars(8);
 
@@ -139,50 +193,150 @@ impl Icon {
     }
 }
 
-/// RDP command line for dry-run output (KRDP server via sdl-freerdp3).
-fn rdp_command_line(host: &str, user: &str) -> String {
-    format!(
-        "sdl-freerdp3 /u:{} /w:800 /h:480 /f /cert:ignore +multitouch /v:{}",
-        user, host
-    )
+/// Touch-sized brightness slider writing through `backlight`. The level is
+/// remembered when the finger lifts, so it survives restarts and screen-off.
+/// Each time it is shown it catches up with the current level, which
+/// auto-brightness, the schedule or the other process may have changed.
+fn brightness_slider(backlight: &Rc<Backlight>) -> GtkBox {
+    let box_ = GtkBox::new(Orientation::Horizontal, 8);
+    let icon = Image::from_icon_name(Some("display-brightness-symbolic"), IconSize::LargeToolbar);
+    box_.pack_start(&icon, false, false, 0);
+
+    let scale = gtk::Scale::with_range(Orientation::Horizontal, MIN_BRIGHTNESS_PERCENT, 100.0, 5.0);
+    scale.set_draw_value(false);
+    scale.set_size_request(BRIGHTNESS_SLIDER_WIDTH, 48);
+    // Set while the slider follows the backlight, not the other way round
+    let syncing = Rc::new(Cell::new(false));
+    let sync = {
+        let bl = backlight.clone();
+        let syncing = syncing.clone();
+        move |s: &gtk::Scale| {
+            if let Ok(percent) = bl.percent() {
+                syncing.set(true);
+                s.set_value(percent as f64);
+                syncing.set(false);
+            }
+        }
+    };
+    sync(&scale);
+    scale.connect_map(sync);
+    let bl = backlight.clone();
+    scale.connect_value_changed(move |s| {
+        if syncing.get() {
+            return;
+        }
+        if let Err(e) = bl.set_percent(s.value().round() as u32) {
+            log(&format!("Setting brightness failed: {}", e));
+        }
+    });
+    let bl = backlight.clone();
+    scale.connect_button_release_event(move |_, _| {
+        match bl.level().and_then(|level| bl.remember(level)) {
+            Ok(()) => log(&format!("Brightness set to {}%", bl.percent().unwrap_or_default())),
+            Err(e) => log(&format!("Saving brightness failed: {}", e)),
+        }
+        gtk::Inhibit(false)
+    });
+    box_.pack_start(&scale, true, true, 0);
+    box_
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Home-screen brightness control, anchored bottom-right.
+fn create_brightness_control(backlight: &Rc<Backlight>) -> Window {
+    let window = Window::new(WindowType::Toplevel);
+    window.set_decorated(false);
+    window.set_skip_taskbar_hint(true);
 
-    #[test]
-    fn rdp_command_has_host() {
-        let cmd = rdp_command_line("10.1.1.3", "tv");
-        assert!(cmd.contains("/v:10.1.1.3"), "missing /v:host: {}", cmd);
-        assert!(cmd.contains("sdl-freerdp3"), "must use sdl-freerdp3: {}", cmd);
-        assert!(cmd.contains("/w:800"), "missing width: {}", cmd);
-        assert!(cmd.contains("/h:480"), "missing height: {}", cmd);
-        assert!(cmd.contains("/f"), "missing fullscreen: {}", cmd);
-        assert!(cmd.contains("/u:tv"), "missing user: {}", cmd);
-        assert!(cmd.contains("+multitouch"), "missing multitouch: {}", cmd);
-    }
+    gtk_layer_shell::init_for_window(&window);
+    gtk_layer_shell::set_layer(&window, Layer::Top);
+    gtk_layer_shell::set_anchor(&window, Edge::Bottom, true);
+    gtk_layer_shell::set_anchor(&window, Edge::Right, true);
+    gtk_layer_shell::set_margin(&window, Edge::Bottom, ICON_MARGIN);
+    gtk_layer_shell::set_margin(&window, Edge::Right, ICON_MARGIN);
+
+    let slider = brightness_slider(backlight);
+    slider.set_margin_start(12);
+    slider.set_margin_end(12);
+    window.add(&slider);
+    window.show_all();
+    window
+}
+
+/// RDP command line for dry-run output (KRDP server via sdl-freerdp3).
+fn rdp_command_line(profile: &Profile, fingerprint: Option<&str>) -> String {
+    format!("{} {}", freerdp::FREERDP_BIN, freerdp::connect_args(profile, fingerprint).join(" "))
 }
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("credential") {
+        std::process::exit(credentials::run_cli(&args[2..]));
+    }
+    let mote_view = args.iter().any(|a| a == "--mote-view");
+    // Optional profile name after --mote-view; the first profile otherwise
+    let profile_name = args
+        .iter()
+        .skip_while(|a| *a != "--mote-view")
+        .nth(1)
+        .filter(|a| !a.starts_with("--"))
+        .cloned();
+    let profiles = profiles::load_profiles();
+
     if env::var("MOTE_DRY_RUN").is_ok() {
-        let host = env::var("ANDROID_HOST").unwrap_or_else(|_| "10.1.1.3".to_string());
-        let user = env::var("MOTE_RDP_USER").unwrap_or_else(|_| "tv".to_string());
-        println!("{}", rdp_command_line(&host, &user));
+        match profiles::find_profile(&profiles, profile_name.as_deref()) {
+            Some(profile) => {
+                let known = certs::KnownHosts::load();
+                println!("{}", rdp_command_line(&profile, known.get(&profile.name)));
+            }
+            None => {
+                eprintln!("Unknown profile: {}", profile_name.unwrap_or_default());
+                std::process::exit(1);
+            }
+        }
         std::process::exit(0);
     }
 
-    log(&format!("desktop-icons starting, args: {:?}", env::args().collect::<Vec<_>>()));
+    log(&format!("desktop-icons starting, args: {:?}", args));
     
-    if env::args().any(|a| a == "--mote-view") {
+    if mote_view {
         log("--mote-view flag detected, launching Mote (RDP) view");
+        gtk::init().expect("Failed to initialize GTK");
+        let profile = match profiles::find_profile(&profiles, profile_name.as_deref()) {
+            Some(p) => p,
+            None => {
+                show_error(
+                    "Mote failed",
+                    &format!("No connection profile named \"{}\" in profiles.toml", profile_name.unwrap_or_default()),
+                );
+                return;
+            }
+        };
+        // Tapping the icon of a parked session brings it back; any other
+        // parked session is disconnected first
+        let park_dir = Path::new(park::PARK_DIR);
+        match park::parked(park_dir) {
+            Some(name) if name == profile.name => {
+                log(&format!("Session for {} is parked - asking it to resume", name));
+                park::request(park_dir, park::Request::Resume);
+                return;
+            }
+            Some(name) => {
+                log(&format!("Disconnecting parked session for {}", name));
+                park::request(park_dir, park::Request::Disconnect);
+            }
+            None => {}
+        }
         // Create flag file to signal main process to hide icons
         std::fs::write(MOTE_ACTIVE_FLAG, "").ok();
-        gtk::init().expect("Failed to initialize GTK");
-        let _overlay = launch_mote_view();
+        let _overlay = launch_mote_view(&profile);
         gtk::main();
-        // Remove flag file on exit so icons reappear
-        std::fs::remove_file(MOTE_ACTIVE_FLAG).ok();
+        // Remove flag file on exit so icons reappear; a parked session
+        // already gave it up (and another Mote view may own it now)
+        if park::parked(park_dir).as_deref() == Some(profile.name.as_str()) {
+            park::unpark(park_dir);
+        } else {
+            std::fs::remove_file(MOTE_ACTIVE_FLAG).ok();
+        }
         log("Mote view exited, removed mote-active flag");
         return;
     }
@@ -194,53 +348,99 @@ fn main() {
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_else(|_| "/home/m/desktop-icons".to_string());
 
-    let mote = Rc::new(Icon::new(
-        "Mote",
-        "video-display",
-        &format!("{} --mote-view", exe_path),
-        16,
-    ));
-
-    let chromium = Rc::new(Icon::new(
-        "Chromium",
-        "web-browser",
-        "chromium --ozone-platform=wayland",
-        16 + 72 + 12,
-    ));
+    // One Mote icon per connection profile, then the icons from
+    // ~/.config/mote/icons.toml (built-in set when missing), laid out left to
+    // right in `order`.
+    let mut icon_configs: Vec<config::IconConfig> =
+        profiles.iter().map(Profile::icon_config).collect();
+    icon_configs.extend(config::load_icons());
+    icon_configs.sort_by_key(|i| i.order);
+    let icons: Vec<Rc<Icon>> = icon_configs
+        .iter()
+        .enumerate()
+        .map(|(i, cfg)| {
+            Rc::new(Icon::new(
+                &cfg.label,
+                &cfg.icon,
+                &cfg.command.replace(config::EXE_PLACEHOLDER, &exe_path),
+                ICON_MARGIN + (ICON_SIZE + ICON_SPACING) * i as i32,
+            ))
+        })
+        .collect();
+
+    // Brightness control in the bottom-right corner, at the level the user
+    // picked last time
+    let brightness = Backlight::find().map(|backlight| {
+        if let Err(e) = backlight.on() {
+            log(&format!("Restoring backlight level failed: {}", e));
+        }
+        create_brightness_control(&Rc::new(backlight))
+    });
 
-    let shutdown = Rc::new(Icon::new(
-        "Shutdown",
-        "system-shutdown",
-        "sudo shutdown -h now",
-        16 + (72 + 12) * 2,
-    ));
+    // Blank the home screen too when nobody uses it; Mote view runs its own
+    // timer while it is up
+    let last_activity = Rc::new(Cell::new(Instant::now()));
+    let screen_is_off = Rc::new(Cell::new(false));
+    let wake = Rc::new(create_wake_overlay(&last_activity, &screen_is_off, || {}));
+    start_idle_timer(
+        IdleStages::home_screen(&settings::load_settings().idle),
+        // A touch on a blank home screen must not reach the icon under it
+        WakeMode::Tap,
+        &last_activity,
+        &screen_is_off,
+        &Rc::new(Cell::new(KeepAwake::Off)),
+        move || match Path::new(MOTE_ACTIVE_FLAG).exists() {
+            true => Watch::Nothing,
+            false => Watch::Screen(wake.clone()),
+        },
+        || {},
+    );
 
-    let reboot = Rc::new(Icon::new(
-        "Reboot",
-        "system-reboot",
-        "sudo reboot",
-        16 + (72 + 12) * 3,
-    ));
+    // Resume/Disconnect for a session parked by Mote view's Home button
+    let resume = create_resume_bar();
 
     // Poll for mote-active flag to hide/show icons
-    let icons: Vec<Rc<Icon>> = vec![mote, chromium, shutdown, reboot];
     let icons_hidden = Rc::new(RefCell::new(false));
+    let mut shown_parked: Option<String> = None;
     
     timeout_add_local(Duration::from_millis(500), move || {
         let mote_active = Path::new(MOTE_ACTIVE_FLAG).exists();
         let currently_hidden = *icons_hidden.borrow();
+
+        let parked = park::parked(Path::new(park::PARK_DIR)).filter(|_| !mote_active);
+        // The parked session counts down to disconnecting with our screen
+        if parked.is_some() {
+            park::set_screen_off(Path::new(park::PARK_DIR), screen_is_off.get());
+        }
+        if parked != shown_parked {
+            match &parked {
+                Some(name) => {
+                    log(&format!("Session for {} is parked - offering Resume", name));
+                    resume.label.set_markup(&format!("<b>{}</b> is still connected", glib::markup_escape_text(name)));
+                    resume.window.show_all();
+                }
+                None => resume.window.hide(),
+            }
+            shown_parked = parked;
+        }
         
         if mote_active && !currently_hidden {
             log("Mote active detected, hiding desktop icons");
             for icon in &icons {
                 icon.hide();
             }
+            if let Some(b) = &brightness {
+                b.hide();
+            }
             *icons_hidden.borrow_mut() = true;
         } else if !mote_active && currently_hidden {
             log("Mote inactive, showing desktop icons");
             for icon in &icons {
                 icon.show();
             }
+            if let Some(b) = &brightness {
+                b.show_all();
+            }
             *icons_hidden.borrow_mut() = false;
         }
         Continue(true)
@@ -249,6 +449,55 @@ fn main() {
     gtk::main();
 }
 
+struct ResumeBar {
+    window: Window,
+    label: Label,
+}
+
+/// Home-screen bar for a parked session, anchored bottom-left: Resume
+/// brings Mote view back, Disconnect ends the session. Starts hidden.
+fn create_resume_bar() -> ResumeBar {
+    let window = Window::new(WindowType::Toplevel);
+    window.set_decorated(false);
+    window.set_skip_taskbar_hint(true);
+
+    gtk_layer_shell::init_for_window(&window);
+    gtk_layer_shell::set_layer(&window, Layer::Top);
+    gtk_layer_shell::set_anchor(&window, Edge::Bottom, true);
+    gtk_layer_shell::set_anchor(&window, Edge::Left, true);
+    gtk_layer_shell::set_margin(&window, Edge::Bottom, ICON_MARGIN);
+    gtk_layer_shell::set_margin(&window, Edge::Left, ICON_MARGIN);
+
+    let box_ = GtkBox::new(Orientation::Horizontal, 12);
+    box_.set_margin_start(12);
+    box_.set_margin_end(12);
+    let label = Label::new(None);
+    box_.pack_start(&label, false, false, 0);
+
+    let resume_btn = Button::with_label("Resume");
+    resume_btn.set_size_request(96, 48);
+    let bar = window.clone();
+    resume_btn.connect_clicked(move |_| {
+        log("Resume pressed");
+        park::request(Path::new(park::PARK_DIR), park::Request::Resume);
+        bar.hide();
+    });
+    box_.pack_start(&resume_btn, false, false, 0);
+
+    let disconnect_btn = Button::with_label("Disconnect");
+    disconnect_btn.set_size_request(DISCONNECT_WIDTH, 48);
+    let bar = window.clone();
+    disconnect_btn.connect_clicked(move |_| {
+        log("Disconnect pressed for the parked session");
+        park::request(Path::new(park::PARK_DIR), park::Request::Disconnect);
+        bar.hide();
+    });
+    box_.pack_start(&disconnect_btn, false, false, 0);
+
+    window.add(&box_);
+    ResumeBar { window, label }
+}
+
 fn log(msg: &str) {
     use std::io::Write;
     if let Ok(mut f) = std::fs::OpenOptions::new()
@@ -266,6 +515,13 @@ fn show_error(title: &str, message: &str) {
 
 /// Show error in a layer-shell overlay so it is always on top and visible (not hidden behind other windows).
 fn show_error_overlay(title: &str, message: &str) {
+    show_dialog_overlay(title, message, &["OK"]);
+}
+
+/// Modal layer-shell dialog with one button per label (left to right).
+/// Blocks in a nested main loop; returns the index of the button pressed, or
+/// `None` if the window went away some other way.
+fn show_dialog_overlay(title: &str, message: &str, buttons: &[&str]) -> Option<usize> {
     let window = Window::new(WindowType::Toplevel);
     window.set_title(title);
     window.set_decorated(false);
@@ -321,137 +577,832 @@ fn show_error_overlay(title: &str, message: &str) {
     msg_label.set_max_width_chars(50);
     box_.pack_start(&msg_label, true, true, 0);
 
-    let ok_btn = Button::with_label("OK");
-    ok_btn.set_margin_top(12);
-    let window_clone = window.clone();
-    ok_btn.connect_clicked(move |_| {
-        window_clone.close();
-    });
-    box_.pack_end(&ok_btn, false, false, 0);
+    let response = Rc::new(Cell::new(None));
+    let button_box = GtkBox::new(Orientation::Horizontal, 12);
+    button_box.set_margin_top(12);
+    button_box.set_homogeneous(true);
+    for (i, label) in buttons.iter().enumerate() {
+        let btn = Button::with_label(label);
+        btn.set_size_request(-1, 48);
+        let window_clone = window.clone();
+        let response = response.clone();
+        btn.connect_clicked(move |_| {
+            response.set(Some(i));
+            window_clone.close();
+        });
+        button_box.pack_start(&btn, true, true, 0);
+    }
+    box_.pack_end(&button_box, false, false, 0);
 
     window.add(&box_);
     window.show_all();
     window.present();
 
     main_loop.run();
+    response.get()
+}
+
+/// Act on the certificate check for `profile`, asking the user to accept the
+/// certificate on first connect. Returns the fingerprint to pin FreeRDP to,
+/// or `None` when the connection must not go ahead.
+fn verify_server_certificate(profile: &Profile, check: Result<certs::Check, String>) -> Option<String> {
+    match check {
+        Ok(certs::Check::Trusted(fp)) => {
+            log(&format!("Certificate for {} matches pinned fingerprint", profile.name));
+            Some(fp)
+        }
+        Ok(certs::Check::New(fp)) => {
+            log(&format!("First connection to {}: certificate {}", profile.name, fp));
+            let message = format!(
+                "First connection to {} ({}:{}).\n\nServer certificate SHA-256 fingerprint:\n{}\n\n\
+                 Accept only if this matches the fingerprint shown on the host.",
+                profile.name,
+                profile.host,
+                profile.port,
+                certs::format_fingerprint(&fp)
+            );
+            if show_dialog_overlay("Trust this server?", &message, &["Reject", "Accept"]) != Some(1) {
+                log("Certificate rejected by user");
+                return None;
+            }
+            if let Err(e) = certs::pin(profile, &fp) {
+                log(&format!("Failed to save pinned certificate: {}", e));
+            }
+            Some(fp)
+        }
+        Ok(certs::Check::Changed { pinned, actual }) => {
+            show_error(
+                "Server certificate changed",
+                &format!(
+                    "The certificate of {} ({}:{}) no longer matches the one accepted earlier. \
+                     The connection was refused because someone may be impersonating the host.\n\n\
+                     Accepted: {}\nNow:      {}\n\n\
+                     If the host was reinstalled, remove \"{}\" from {}.",
+                    profile.name,
+                    profile.host,
+                    profile.port,
+                    certs::format_fingerprint(&pinned),
+                    certs::format_fingerprint(&actual),
+                    profile.name,
+                    certs::KnownHosts::path().display()
+                ),
+            );
+            None
+        }
+        Err(e) => {
+            show_error("Connection failed", &e);
+            None
+        }
+    }
 }
 
-fn launch_mote_view() -> MoteOverlay {
-    log("launch_mote_view starting");
+fn launch_mote_view(profile: &Profile) -> OverlaySlot {
+    log(&format!("launch_mote_view starting for profile {}", profile.name));
 
     // No desktop icons in Mote view - only RDP + control overlay
+    let overlay: OverlaySlot = Rc::new(RefCell::new(None));
+    let session: SessionSlot = Rc::new(RefCell::new(None));
+
+    // The splash goes up before anything else, so the tap on the icon shows
+    // at once. The credential lookup and the certificate probe (seconds
+    // against an unreachable host) run on a thread behind it; the result
+    // comes back to the main loop, which then connects
+    let connecting = create_connecting_overlay(&profile.name, &session);
+    connecting.show_all();
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let probe_profile = profile.clone();
+    std::thread::spawn(move || {
+        let checked = probe_profile.password().map(|password| (password, certs::check(&probe_profile)));
+        let _ = tx.send(checked);
+    });
+    let profile = profile.clone();
+    let (slot, controls) = (session, overlay.clone());
+    rx.attach(None, move |checked| {
+        // Cancel hides the splash on its way home
+        if !connecting.is_visible() {
+            log("Cancelled while checking the server");
+            return Continue(false);
+        }
+        match checked {
+            Ok((password, check)) => match verify_server_certificate(&profile, check) {
+                Some(fingerprint) => connect(&profile, password, fingerprint, &slot, &controls, connecting.clone()),
+                // Nothing to show without a trusted server: straight back home
+                None => {
+                    connecting.hide();
+                    return_home();
+                }
+            },
+            Err(e) => {
+                connecting.hide();
+                show_error("Mote failed", &e.to_string());
+                return_home();
+            }
+        }
+        Continue(false)
+    });
+    overlay
+}
 
-    let host = env::var("ANDROID_HOST").unwrap_or_else(|_| "10.1.1.3".to_string());
-    let rdp_user = env::var("MOTE_RDP_USER").unwrap_or_else(|_| "tv".to_string());
-    let rdp_password = env::var("MOTE_RDP_PASSWORD").unwrap_or_else(|_| "k".to_string());
+/// Start the RDP client with a trusted `fingerprint` and follow the session
+/// until Mote view ends, with `connecting` up until the window maps. The
+/// client goes into `session`, the controls into `overlay`.
+fn connect(
+    profile: &Profile,
+    rdp_password: String,
+    fingerprint: String,
+    session: &SessionSlot,
+    overlay: &OverlaySlot,
+    connecting: Window,
+) {
+    let host = profile.host.clone();
 
     let last_activity = Rc::new(Cell::new(Instant::now()));
     let screen_is_off = Rc::new(Cell::new(false));
+    let keep_awake = Rc::new(Cell::new(KeepAwake::Off));
+    // Set by Home; the event pump below does the parking
+    let parked = Rc::new(Cell::new(false));
+    if profile.keep_awake {
+        keep_awake.set(KeepAwake::start(Instant::now(), keep_awake_expiry()));
+        log("Keep awake on for this profile");
+    }
 
-    // Connect to KRDP server on port 3389 (standard RDP). KRDP uses NLA
-    // authentication with username/password. Resolution is set to the 7"
-    // touchscreen native 800x480 via /w: /h: so the server renders at that
-    // size (no client-side scaling needed). +multitouch enables touch
-    // redirection over the RDP protocol.
-    let rdp_cmd = format!(
-        "/u:{} /p:{} /w:800 /h:480 /f /cert:ignore +multitouch /v:{}",
-        rdp_user, rdp_password, host
-    );
-    let child = match Command::new("sdl-freerdp3")
-        .args(rdp_cmd.split_whitespace().collect::<Vec<_>>())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
+    // Window events tell us when the RDP window appears and goes away;
+    // subscribe before spawning so the first one is not missed
+    let sway = match sway_connect().and_then(sway_ipc::Connection::subscribe) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            log(&format!("Sway event subscription failed: {}", e));
+            None
+        }
+    };
+    // Without events, look the window up in the tree now and then; without
+    // sway at all, the supervisor's Connected event has to do
+    let poll_tree = sway.is_none() && find_sway_socket().is_some();
+    if sway.is_none() {
+        log(if poll_tree {
+            "Looking the RDP window up in the sway tree instead"
+        } else {
+            "No sway - following the RDP session state only"
+        });
+    }
+    let mut last_tree_lookup: Option<Instant> = None;
+
+    // Connect to the profile's KRDP server (see Profile::freerdp_args); the
+    // password is handed over on stdin, never in argv.
+    let output = freerdp::OutputLog::new(freerdp::OUTPUT_LOG_LINES);
+    let client = match freerdp::spawn(profile, &fingerprint, &rdp_password, &output) {
         Ok(c) => {
-            log(&format!("Running: sdl-freerdp3 {}", rdp_cmd.replace(&rdp_password, "***")));
+            log(&format!("Running: {}", rdp_command_line(profile, Some(&fingerprint))));
             c
         }
         Err(e) => {
             log(&format!("Failed to start sdl-freerdp3: {}", e));
+            connecting.hide();
             show_error(
                 "Mote failed",
                 "Could not start RDP client. Install: sudo apt install freerdp3-sdl",
             );
-            return create_control_overlay(&host, &last_activity, &screen_is_off);
+            return_home();
+            return;
         }
     };
 
-    let start = Instant::now();
-    let mut child = child;
-    let mut stderr = child.stderr.take();
-    std::thread::spawn(move || {
-        let status = child.wait();
-        let elapsed = start.elapsed();
-        if elapsed < Duration::from_secs(10) {
-            if let Ok(exit_status) = status {
-                if !exit_status.success() {
-                    let mut err_text = String::new();
-                    if let Some(ref mut s) = stderr {
-                        let _ = s.read_to_string(&mut err_text);
+    // The supervisor watches the client for the whole session and respawns
+    // it with the same settings when an established session drops.
+    let target = format!("{}:{}", profile.host, profile.port);
+    let relaunch_profile = profile.clone();
+    let relaunch_output = output.clone();
+    let relaunch = move || freerdp::spawn(&relaunch_profile, &fingerprint, &rdp_password, &relaunch_output);
+    *session.borrow_mut() = Some(Supervisor::start(
+        client,
+        relaunch,
+        output,
+        ReconnectPolicy::new(profile.reconnect_attempts),
+    ));
+
+    log("Creating overlay windows");
+    let reconnect = create_reconnect_overlay(session);
+
+    // Follow sway and the supervisor: controls once the RDP window maps,
+    // reconnect status, final errors, and home once the window has closed
+    let max_attempts = profile.reconnect_attempts;
+    let la = last_activity.clone();
+    let sio = screen_is_off.clone();
+    let ka = keep_awake.clone();
+    let idle_parked = parked.clone();
+    let profile_name = profile.name.clone();
+    let events = session.clone();
+    let controls = overlay.clone();
+    let mut rdp_window: Option<i64> = None;
+    let mut leaving_since: Option<Instant> = None;
+    // Long screen-off closes the client; waking connects again
+    let suspend_mins = settings::load_settings().idle.disconnect_when_off_mins;
+    let suspend_after = (suspend_mins > 0).then(|| Duration::from_secs(suspend_mins * 60));
+    let mut off_since: Option<Instant> = None;
+    let mut was_parked = false;
+    timeout_add_local(Duration::from_millis(200), move || {
+        let client_pid = events.borrow().as_ref().map(Supervisor::client_pid);
+        while let Some(event) = sway.as_ref().and_then(Subscription::try_event) {
+            match event {
+                SwayEvent::Window { change: WindowChange::New | WindowChange::Focus, container }
+                    if container.pid.is_some() && container.pid == client_pid && rdp_window != Some(container.id) =>
+                {
+                    log(&format!("RDP window {} mapped", container.id));
+                    rdp_window = Some(container.id);
+                    if parked.get() {
+                        // Reconnected while parked: keep it out of sight
+                        sway_command(&format!("[con_id={}] move container to workspace {}", container.id, PARKED_WORKSPACE));
+                    } else {
+                        connecting.hide();
+                        show_controls(&controls, &host, &events, &last_activity, &screen_is_off, &keep_awake, &parked);
                     }
-                    let err_trim = err_text.trim();
-                    let msg = if err_trim.is_empty() {
-                        "Could not connect to KRDP. Check host, port 3389, and credentials."
-                            .to_string()
+                }
+                SwayEvent::Window { change: WindowChange::Close, container } if Some(container.id) == rdp_window => {
+                    log(&format!("RDP window {} closed", container.id));
+                    rdp_window = None;
+                }
+                SwayEvent::Workspace { change: WorkspaceChange::Focus, current } => {
+                    log(&format!("Workspace focused: {:?}", current.and_then(|w| w.name)));
+                }
+                _ => {}
+            }
+        }
+        if poll_tree && last_tree_lookup.is_none_or(|t| t.elapsed() >= TREE_POLL_INTERVAL) {
+            last_tree_lookup = Some(Instant::now());
+            match client_pid.and_then(find_window_by_pid) {
+                Some(id) if rdp_window != Some(id) => {
+                    log(&format!("RDP window {} found in tree", id));
+                    rdp_window = Some(id);
+                    if parked.get() {
+                        sway_command(&format!("[con_id={}] move container to workspace {}", id, PARKED_WORKSPACE));
                     } else {
-                        format!("RDP connection to KRDP failed:\n{}", err_trim)
-                    };
-                    let title = "Connection failed".to_string();
-                    let _ = glib::idle_add(move || {
-                        show_error(&title, &msg);
-                        Continue(false)
-                    });
+                        connecting.hide();
+                        show_controls(&controls, &host, &events, &last_activity, &screen_is_off, &keep_awake, &parked);
+                    }
+                }
+                None if rdp_window.is_some() => rdp_window = None,
+                _ => {}
+            }
+        }
+
+        // Leaving: the supervisor kills the client; go home once its window is gone
+        if events.borrow().as_ref().is_some_and(Supervisor::is_stopped) {
+            let since = *leaving_since.get_or_insert_with(Instant::now);
+            if rdp_window.is_none() || since.elapsed() >= WINDOW_CLOSE_TIMEOUT {
+                return_home();
+                return Continue(false);
+            }
+            return Continue(true);
+        }
+
+        // Home parks the RDP window and hands the screen back to the home
+        // screen, which can ask for it back or for a disconnect
+        if parked.get() != was_parked {
+            if parked.get() && !park_mote_view(rdp_window, &controls, &profile_name) {
+                log("Cannot park the session - disconnecting instead");
+                parked.set(false);
+                exit_mote_view(&events);
+                return Continue(true);
+            }
+            if !parked.get() {
+                resume_mote_view(rdp_window, &controls);
+            }
+            was_parked = parked.get();
+        }
+        if parked.get() {
+            match park::take_request(Path::new(park::PARK_DIR)) {
+                Some(park::Request::Resume) => {
+                    log("Resume requested");
+                    // Asked for from a lit home screen
+                    screen_is_off.set(false);
+                    parked.set(false);
+                }
+                Some(park::Request::Disconnect) => {
+                    log("Disconnect requested for the parked session");
+                    exit_mote_view(&events);
+                }
+                None => screen_is_off.set(park::screen_off(Path::new(park::PARK_DIR))),
+            }
+        }
+
+        if let (Some(after), Some(s)) = (suspend_after, events.borrow().as_ref()) {
+            if screen_is_off.get() {
+                let since = *off_since.get_or_insert_with(Instant::now);
+                if !s.is_suspended() && since.elapsed() >= after {
+                    log(&format!("Screen off for {:?} - disconnecting until it wakes", after));
+                    s.suspend();
+                }
+            } else {
+                off_since = None;
+                if s.is_suspended() {
+                    log("Screen woke - reconnecting");
+                    reconnect.window.hide();
+                    if !parked.get() {
+                        connecting.show_all();
+                    }
+                    s.resume();
+                }
+            }
+        }
+
+        let event = events.borrow().as_ref().and_then(Supervisor::try_event);
+        match event {
+            Some(session::Event::Connected) => {
+                log("RDP session up");
+                // Also covers running without sway events
+                connecting.hide();
+                reconnect.window.hide();
+                show_controls(&controls, &host, &events, &last_activity, &screen_is_off, &keep_awake, &parked);
+            }
+            Some(session::Event::Reconnecting { attempt, delay, reason }) => {
+                let mut text = format!(
+                    "<b>Connection lost</b>\nReconnecting… (attempt {} of {}, in {}s)",
+                    attempt,
+                    max_attempts,
+                    delay.as_secs()
+                );
+                if let Some(reason) = reason {
+                    text.push_str(&format!("\n<small>{}</small>", glib::markup_escape_text(&reason)));
+                }
+                reconnect.label.set_markup(&text);
+                if !parked.get() {
+                    reconnect.window.show_all();
+                    reconnect.window.present();
                 }
             }
+            Some(session::Event::Failed(failure)) => {
+                connecting.hide();
+                reconnect.window.hide();
+                show_error(failure.kind.title(), &failure.describe(&target));
+                exit_mote_view(&events);
+            }
+            Some(session::Event::GaveUp { attempts, last_error }) => {
+                reconnect.window.hide();
+                show_error(
+                    last_error.kind.title(),
+                    &format!("Gave up after {} reconnect attempts.\n\n{}", attempts, last_error.describe(&target)),
+                );
+                exit_mote_view(&events);
+            }
+            Some(session::Event::Ended) => {
+                log("RDP session ended - returning home");
+                exit_mote_view(&events);
+            }
+            None => {}
         }
+        Continue(true)
     });
 
-    std::thread::sleep(Duration::from_millis(1000));
+    // Idle stages from settings.toml, counted once the controls are up
+    let idle_session = session.clone();
+    let wake_source = overlay.clone();
+    let background_parked = idle_parked.clone();
+    let idle = settings::load_settings().idle;
+    start_idle_timer(
+        IdleStages::from_settings(&idle),
+        idle.wake,
+        &la,
+        &sio,
+        &ka,
+        // Parked, the home screen's own timer has the screen; this one only
+        // counts down to disconnecting
+        move || match wake_source.borrow().as_ref() {
+            None => Watch::Nothing,
+            Some(o) if background_parked.get() => Watch::Background(o.wake_overlay.clone()),
+            Some(o) => Watch::Screen(o.wake_overlay.clone()),
+        },
+        move || {
+            // The home screen comes back lit and counts down on its own
+            if !idle_parked.get() {
+                wake_screen();
+            }
+            exit_mote_view(&idle_session);
+        },
+    );
 
-    log("Creating overlay windows");
-    let overlay = create_control_overlay(&host, &last_activity, &screen_is_off);
+    log("RDP launched, waiting for its window");
+}
 
-    let screen_off_delay = env::var("SCREEN_OFF_DELAY")
-        .unwrap_or_else(|_| "120".to_string())
-        .parse::<u64>()
-        .unwrap_or(120);
+/// What the idle timer watches over.
+enum Watch {
+    /// Nothing yet (Mote view still connecting) or nothing of ours (home
+    /// screen covered by Mote view); idle time counts from when watching
+    /// starts again.
+    Nothing,
+    /// The screen, with the wake overlay to put up when it blanks.
+    Screen(Rc<Window>),
+    /// Input only, for disconnecting: another process has the screen.
+    Background(Rc<Window>),
+}
 
-    // Periodically check for inactivity; turn screen off when idle too long,
-    // turn it back on automatically isn't needed (wake overlay handles that).
+/// Idle stages on the main loop: dim, then blank behind the wake overlay
+/// (or show a screensaver), then `on_disconnect`, with the display schedule
+/// from settings.toml on top and auto-brightness while in use. `IdleTimer`
+/// decides; this carries it out, with `watch` saying what there is to watch
+/// at each tick. With `WakeMode::Input`, input wakes the screen as soon as
+/// it comes.
+fn start_idle_timer(
+    stages: IdleStages,
+    wake: WakeMode,
+    last_activity: &Rc<Cell<Instant>>,
+    screen_is_off: &Rc<Cell<bool>>,
+    keep_awake: &Rc<Cell<KeepAwake>>,
+    watch: impl Fn() -> Watch + 'static,
+    on_disconnect: impl Fn() + 'static,
+) {
+    log(&format!("Idle stages: {:?}", stages));
+    let backlight = Backlight::find();
+    // Touches inside the RDP session count too, not just taps on overlays
+    let (resumed_tx, resumed_rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let input = InputMonitor::start(move || {
+        // Nobody listening any more is fine
+        let _ = resumed_tx.send(());
+    });
+    if let Some(monitor) = &input {
+        log(&format!("Tracking input activity via {:?}", monitor.source));
+    }
     let la = last_activity.clone();
     let sio = screen_is_off.clone();
-    let wake_overlay = overlay.wake_overlay.clone();
-    timeout_add_local(Duration::from_secs(10), move || {
-        if !sio.get() && la.get().elapsed() >= Duration::from_secs(screen_off_delay) {
-            log("Inactivity timeout - turning screen off");
+    let ka = keep_awake.clone();
+    let settings = settings::load_settings();
+    let mut auto_brightness = AutoBrightness::new(&settings.auto_brightness, Path::new(ambient::IIO_ROOT));
+    if auto_brightness.is_some() {
+        log("Auto-brightness on");
+    }
+    let schedule = Schedule::from_settings(&settings.schedule).unwrap_or_else(|e| {
+        log(&format!("Invalid schedule in settings.toml: {} - ignoring it", e));
+        Schedule::default()
+    });
+    // Photo frame (while there are photos) or clock instead of a black screen
+    let saver_settings = settings.screensaver.clone();
+    let saver = match saver_settings.mode {
+        ScreensaverMode::Blank => None,
+        ScreensaverMode::Photos => Some(create_photo_frame(&saver_settings, last_activity, screen_is_off)),
+        ScreensaverMode::Clock => Some(create_clock_screensaver(&saver_settings, last_activity, screen_is_off)),
+    };
+    // Pass-through wake needs to see input without the overlay
+    let pass_through = match (wake, &input) {
+        (WakeMode::Input, Some(_)) => true,
+        (WakeMode::Input, None) => {
+            log("Wake on input needs an input source - using the wake overlay");
+            false
+        }
+        (WakeMode::Tap, _) => false,
+    };
+    let mut timer = IdleTimer::new(stages, schedule.wake_for, pass_through, saver.is_some());
+    let mut watching = false;
+    let tick = move || {
+        let (wake_overlay, background) = match watch() {
+            Watch::Nothing => {
+                watching = false;
+                return Continue(true);
+            }
+            Watch::Screen(w) => (w, false),
+            Watch::Background(w) => (w, true),
+        };
+        if !watching {
+            watching = true;
+            la.set(Instant::now());
+        }
+        let step = timer.step(Tick {
+            now: Instant::now(),
+            last_activity: la.get(),
+            last_input: input.as_ref().map(InputMonitor::last_input),
+            keep_awake: ka.get(),
+            screen_off: sio.get(),
+            screensaver_up: saver.as_ref().is_some_and(|f| f.is_visible()),
+            scheduled: schedule.at(chrono::Local::now().time()),
+            background,
+        });
+        la.set(step.last_activity);
+        sio.set(step.screen_off);
+        let blank = |wake_overlay: &Window| {
+            if let Some(frame) = saver.as_ref().filter(|f| f.is_visible()) {
+                frame.hide();
+            }
             turn_screen_off();
-            wake_overlay.show_all();
-            sio.set(true);
+            if !pass_through {
+                wake_overlay.show_all();
+            }
+        };
+        for action in step.actions {
+            match action {
+                Action::KeepAwakeExpired => {
+                    log("Keep awake expired");
+                    ka.set(KeepAwake::Off);
+                }
+                Action::Wake { scheduled } => {
+                    if scheduled {
+                        log("Scheduled screen-off hours over - waking screen");
+                        wake_overlay.hide();
+                    } else {
+                        log("Input while blank - waking screen");
+                    }
+                    wake_screen();
+                }
+                Action::Restore => {
+                    log("Activity while dimmed - restoring brightness");
+                    if let Some(Err(e)) = backlight.as_ref().map(Backlight::on) {
+                        log(&format!("Restoring brightness failed: {}", e));
+                    }
+                }
+                Action::Dim(percent) => {
+                    log(&format!("Idle - dimming to {}%", percent));
+                    match &backlight {
+                        Some(bl) => {
+                            if let Err(e) = bl.dim(percent) {
+                                log(&format!("Dimming failed: {}", e));
+                            }
+                        }
+                        None => log("No backlight - cannot dim"),
+                    }
+                }
+                Action::ScreenOff { scheduled } => {
+                    if scheduled {
+                        log("Scheduled screen-off hours - turning screen off");
+                    } else {
+                        log("Inactivity timeout - turning screen off");
+                    }
+                    blank(&wake_overlay);
+                }
+                Action::Screensaver => {
+                    let ready = match saver_settings.mode {
+                        ScreensaverMode::Photos => !screensaver::list_photos(&saver_settings.photo_dir()).is_empty(),
+                        _ => true,
+                    };
+                    match saver.as_ref().filter(|_| ready) {
+                        Some(frame) => {
+                            log(&format!("Inactivity timeout - starting {:?} screensaver", saver_settings.mode));
+                            if let Some(Err(e)) = backlight.as_ref().map(|bl| bl.dim(saver_settings.brightness)) {
+                                log(&format!("Dimming for the screensaver failed: {}", e));
+                            }
+                            frame.show_all();
+                        }
+                        None => {
+                            log("Inactivity timeout - turning screen off");
+                            blank(&wake_overlay);
+                        }
+                    }
+                }
+                Action::AutoBrightness => {
+                    let Some(bl) = &backlight else { continue };
+                    match auto_brightness.as_mut().map(|a| a.update(bl)) {
+                        Some(Ok(Some(percent))) => log(&format!("Auto-brightness {}%", percent)),
+                        Some(Err(e)) => {
+                            log(&format!("Auto-brightness failed: {} - turning it off", e));
+                            auto_brightness = None;
+                        }
+                        _ => {}
+                    }
+                }
+                Action::Cap { percent, starts } => {
+                    let Some(bl) = &backlight else { continue };
+                    if starts {
+                        log(&format!("Scheduled brightness cap {}%", percent));
+                    }
+                    if let Err(e) = bl.dim(percent) {
+                        log(&format!("Capping brightness failed: {}", e));
+                    }
+                }
+                Action::Uncap => {
+                    let Some(bl) = &backlight else { continue };
+                    log("Scheduled brightness cap over - restoring brightness");
+                    if let Err(e) = bl.on() {
+                        log(&format!("Restoring brightness failed: {}", e));
+                    }
+                }
+                Action::Disconnect => {
+                    log("Idle too long - disconnecting");
+                    on_disconnect();
+                    return Continue(false);
+                }
+            }
         }
         Continue(true)
+    };
+    // Once a second, and right away when input wakes a blank screen
+    let tick = Rc::new(RefCell::new(tick));
+    let on_resume = tick.clone();
+    let sio = screen_is_off.clone();
+    resumed_rx.attach(None, move |()| match on_resume.try_borrow_mut() {
+        Ok(mut tick) if pass_through && sio.get() => tick(),
+        _ => Continue(true),
     });
+    timeout_add_local(Duration::from_secs(1), move || match tick.try_borrow_mut() {
+        Ok(mut tick) => tick(),
+        Err(_) => Continue(true),
+    });
+}
 
-    log("RDP launched, overlay created");
-    overlay
+/// Create the control overlay (Home and Disconnect buttons, top trigger, wake
+/// overlay) the first time the RDP window is up.
+fn show_controls(
+    slot: &OverlaySlot,
+    host: &str,
+    session: &SessionSlot,
+    last_activity: &Rc<Cell<Instant>>,
+    screen_is_off: &Rc<Cell<bool>>,
+    keep_awake: &Rc<Cell<KeepAwake>>,
+    parked: &Rc<Cell<bool>>,
+) {
+    if slot.borrow().is_none() {
+        let overlay = create_control_overlay(host, session, last_activity, screen_is_off, keep_awake, parked);
+        *slot.borrow_mut() = Some(overlay);
+    }
+}
+
+/// Leave Mote view: stop the session so it is not reconnected. The
+/// supervisor kills the client and the event pump in `launch_mote_view`
+/// returns home once its window has closed; without a session there is
+/// nothing to wait for.
+fn exit_mote_view(session: &SessionSlot) {
+    match session.borrow().as_ref() {
+        Some(s) => s.stop(),
+        None => return_home(),
+    }
+}
+
+fn return_home() {
+    sway_command(&format!("workspace {}", HOME_WORKSPACE));
+    gtk::main_quit();
+}
+
+/// Move the RDP window to the parked workspace, hide the Mote view overlays
+/// and give the screen back to the home screen. False when there is no
+/// window to park or sway would not move it.
+fn park_mote_view(rdp_window: Option<i64>, overlay: &OverlaySlot, profile: &str) -> bool {
+    let Some(id) = rdp_window else {
+        return false;
+    };
+    if !sway_command(&format!("[con_id={}] move container to workspace {}", id, PARKED_WORKSPACE)) {
+        return false;
+    }
+    sway_command(&format!("workspace {}", HOME_WORKSPACE));
+    if let Some(o) = overlay.borrow().as_ref() {
+        o.control_window.hide();
+        o.trigger_window.hide();
+        o.wake_overlay.hide();
+        o.keep_awake_badge.hide();
+    }
+    park::park(Path::new(park::PARK_DIR), profile);
+    std::fs::remove_file(MOTE_ACTIVE_FLAG).ok();
+    log(&format!("Session for {} parked on workspace {}", profile, PARKED_WORKSPACE));
+    true
+}
+
+/// Bring a parked session back: hide the home screen, return the RDP window
+/// and show the controls briefly.
+fn resume_mote_view(rdp_window: Option<i64>, overlay: &OverlaySlot) {
+    std::fs::write(MOTE_ACTIVE_FLAG, "").ok();
+    park::unpark(Path::new(park::PARK_DIR));
+    if let Some(id) = rdp_window {
+        sway_command(&format!("[con_id={}] move container to workspace {}", id, HOME_WORKSPACE));
+        sway_command(&format!("[con_id={}] focus", id));
+    }
+    if let Some(o) = overlay.borrow().as_ref() {
+        o.trigger_window.show_all();
+        o.control_window.show_all();
+        o.control_window.present();
+        let win_hide = o.control_window.clone();
+        timeout_add_local(Duration::from_secs(5), move || {
+            win_hide.hide();
+            Continue(false)
+        });
+    }
+    log("Parked session resumed");
+}
+
+/// Full-screen "Connecting to <profile>…" view with a spinner, shown from
+/// launch until the RDP window maps. Cancel gives up and goes home.
+fn create_connecting_overlay(profile_name: &str, session: &SessionSlot) -> Window {
+    let window = Window::new(WindowType::Toplevel);
+    window.set_decorated(false);
+    window.set_keep_above(true);
+
+    let bg = RGBA::new(0.1, 0.1, 0.1, 1.0);
+    unsafe {
+        gtk::ffi::gtk_widget_override_background_color(
+            window.upcast_ref::<gtk::Widget>().to_glib_none().0,
+            gtk::StateFlags::NORMAL.bits(),
+            bg.to_glib_none().0 as *const _,
+        );
+    }
+
+    gtk_layer_shell::init_for_window(&window);
+    gtk_layer_shell::set_layer(&window, Layer::Overlay);
+    gtk_layer_shell::set_anchor(&window, Edge::Top, true);
+    gtk_layer_shell::set_anchor(&window, Edge::Bottom, true);
+    gtk_layer_shell::set_anchor(&window, Edge::Left, true);
+    gtk_layer_shell::set_anchor(&window, Edge::Right, true);
+    gtk_layer_shell::set_exclusive_zone(&window, -1);
+
+    let box_ = GtkBox::new(Orientation::Vertical, 24);
+    box_.set_valign(gtk::Align::Center);
+    box_.set_halign(gtk::Align::Center);
+
+    let spinner = gtk::Spinner::new();
+    spinner.set_size_request(64, 64);
+    spinner.start();
+    box_.pack_start(&spinner, false, false, 0);
+
+    let label = Label::new(None);
+    label.set_markup(&format!(
+        "<span size='x-large' foreground='white'>Connecting to {}…</span>",
+        glib::markup_escape_text(profile_name)
+    ));
+    box_.pack_start(&label, false, false, 0);
+
+    let cancel_btn = Button::with_label("Cancel");
+    cancel_btn.set_size_request(160, 56);
+    cancel_btn.set_halign(gtk::Align::Center);
+    let session = session.clone();
+    let splash = window.clone();
+    cancel_btn.connect_clicked(move |_| {
+        log("Connect cancelled - exiting Mote view");
+        splash.hide();
+        exit_mote_view(&session);
+    });
+    box_.pack_start(&cancel_btn, false, false, 0);
+
+    window.add(&box_);
+    window
+}
+
+struct ReconnectOverlay {
+    window: Window,
+    label: Label,
+}
+
+/// Status banner shown while a dropped session is being reconnected, with a
+/// Home button to give up. Starts hidden.
+fn create_reconnect_overlay(session: &SessionSlot) -> ReconnectOverlay {
+    let window = Window::new(WindowType::Toplevel);
+    window.set_decorated(false);
+    window.set_default_size(420, 96);
+    window.set_keep_above(true);
+
+    let bg = RGBA::new(0.95, 0.95, 0.95, 1.0);
+    unsafe {
+        gtk::ffi::gtk_widget_override_background_color(
+            window.upcast_ref::<gtk::Widget>().to_glib_none().0,
+            gtk::StateFlags::NORMAL.bits(),
+            bg.to_glib_none().0 as *const _,
+        );
+    }
+
+    // No anchors: the compositor centers the banner on screen
+    gtk_layer_shell::init_for_window(&window);
+    gtk_layer_shell::set_layer(&window, Layer::Overlay);
+    gtk_layer_shell::set_exclusive_zone(&window, 0);
+
+    let box_ = GtkBox::new(Orientation::Horizontal, 16);
+    box_.set_margin_top(16);
+    box_.set_margin_bottom(16);
+    box_.set_margin_start(24);
+    box_.set_margin_end(24);
+
+    let label = Label::new(Some("Reconnecting…"));
+    label.set_line_wrap(true);
+    box_.pack_start(&label, true, true, 0);
+
+    let home_btn = Button::with_label("Home");
+    home_btn.set_size_request(96, 48);
+    let session = session.clone();
+    home_btn.connect_clicked(move |_| {
+        log("Home pressed while reconnecting - exiting Mote view");
+        exit_mote_view(&session);
+    });
+    box_.pack_end(&home_btn, false, false, 0);
+
+    window.add(&box_);
+    ReconnectOverlay { window, label }
 }
 
 fn create_control_overlay(
     host: &str,
+    session: &SessionSlot,
     last_activity: &Rc<Cell<Instant>>,
     screen_is_off: &Rc<Cell<bool>>,
+    keep_awake: &Rc<Cell<KeepAwake>>,
+    parked: &Rc<Cell<bool>>,
 ) -> MoteOverlay {
     let _ = host; // Used by volume buttons when re-enabled
     let window = Window::new(WindowType::Toplevel);
     window.set_decorated(false);
     
-    // Control panel: Home only (volume buttons commented out below)
-    const CONTROL_WIDTH: i32 = 96;
+    // Control panel: brightness + Keep awake + Home + Disconnect (volume buttons commented out below)
+    const CONTROL_WIDTH: i32 = 96 + KEEP_AWAKE_WIDTH + 12 + DISCONNECT_WIDTH + 12;
     const CONTROL_HEIGHT: i32 = 56;
     const SCREEN_WIDTH: i32 = 800;
-    let margin_left = (SCREEN_WIDTH - CONTROL_WIDTH) / 2;
+    let backlight = Backlight::find().map(Rc::new);
+    let control_width = match backlight {
+        Some(_) => CONTROL_WIDTH + BRIGHTNESS_SLIDER_WIDTH + 48,
+        None => CONTROL_WIDTH,
+    };
+    let margin_left = (SCREEN_WIDTH - control_width) / 2;
     
-    window.set_default_size(CONTROL_WIDTH, CONTROL_HEIGHT);
-    window.set_size_request(CONTROL_WIDTH, CONTROL_HEIGHT);
+    window.set_default_size(control_width, CONTROL_HEIGHT);
+    window.set_size_request(control_width, CONTROL_HEIGHT);
     window.set_keep_above(true);
     window.set_skip_taskbar_hint(true);
 
@@ -473,7 +1424,7 @@ fn create_control_overlay(
     // both volume button blocks, and set CONTROL_WIDTH back to 240.
     //
     // let ssh_user = env::var("MOTE_RDP_USER").unwrap_or_else(|_| "tv".to_string());
-    // let ssh_password = env::var("MOTE_RDP_PASSWORD").unwrap_or_else(|_| "k".to_string());
+    // let ssh_password = credentials::lookup("ssh").unwrap_or_default();
     // let host = host.to_string();
     //
     // let vol_down_btn = Button::new();
@@ -541,18 +1492,50 @@ fn create_control_overlay(
     // button_box.pack_start(&vol_up_btn, false, false, 0);
     // --- End volume buttons ---
 
-    // Home button - exits Mote view (kills RDP client)
+    if let Some(backlight) = &backlight {
+        button_box.pack_start(&brightness_slider(backlight), true, true, 0);
+    }
+
+    // Keep awake - holds off dimming and blanking while watching
+    let keep_awake_btn = gtk::ToggleButton::with_label("Keep awake");
+    keep_awake_btn.set_size_request(KEEP_AWAKE_WIDTH, 48);
+    keep_awake_btn.set_active(keep_awake.get().active(Instant::now()));
+    let ka = keep_awake.clone();
+    keep_awake_btn.connect_toggled(move |b| {
+        let now = Instant::now();
+        if !b.is_active() {
+            if ka.get() != KeepAwake::Off {
+                log("Keep awake off");
+            }
+            ka.set(KeepAwake::Off);
+        } else if !ka.get().active(now) {
+            ka.set(KeepAwake::start(now, keep_awake_expiry()));
+            log(&format!("Keep awake on: {:?}", ka.get().remaining(now)));
+        }
+    });
+    button_box.pack_start(&keep_awake_btn, false, false, 0);
+
+    // Home button - parks the session (RDP client keeps running)
     let home_btn = Button::new();
     home_btn.set_label("Home");
     home_btn.set_size_request(64, 48);
+    let park = parked.clone();
     home_btn.connect_clicked(move |_| {
-        log("Home pressed - exiting Mote view");
-        Command::new("pkill").args(["-f", "sdl-freerdp3"]).spawn().ok();
-        Command::new("swaymsg").args(["workspace", "1"]).spawn().ok();
-        gtk::main_quit();
+        log("Home pressed - parking the session");
+        park.set(true);
     });
     button_box.pack_start(&home_btn, false, false, 0);
 
+    // Disconnect button - exits Mote view (kills RDP client)
+    let disconnect_btn = Button::with_label("Disconnect");
+    disconnect_btn.set_size_request(DISCONNECT_WIDTH, 48);
+    let session = session.clone();
+    disconnect_btn.connect_clicked(move |_| {
+        log("Disconnect pressed - exiting Mote view");
+        exit_mote_view(&session);
+    });
+    button_box.pack_start(&disconnect_btn, false, false, 0);
+
     window.add(&button_box);
     window.show_all();
     window.present();
@@ -570,23 +1553,92 @@ fn create_control_overlay(
     let trigger = create_top_trigger(&window_rc, last_activity);
     let trigger_rc = Rc::new(trigger);
     
+    // Badge that stays up while keep awake is on (the panel auto-hides);
+    // also follows the idle timer turning keep awake off when it expires
+    let badge = Rc::new(create_keep_awake_badge());
+    let badge_label = badge.child().and_then(|c| c.downcast::<Label>().ok());
+    let badge_sync = badge.clone();
+    let ka = keep_awake.clone();
+    let badge_parked = parked.clone();
+    timeout_add_local(Duration::from_secs(1), move || {
+        let now = Instant::now();
+        let state = ka.get();
+        if keep_awake_btn.is_active() != state.active(now) {
+            keep_awake_btn.set_active(state.active(now));
+        }
+        if !state.active(now) || badge_parked.get() {
+            badge_sync.hide();
+            return Continue(true);
+        }
+        let text = match state.remaining(now) {
+            Some(left) => format!("Awake · {} min", left.as_secs().div_ceil(60)),
+            None => "Awake".to_string(),
+        };
+        if let Some(label) = &badge_label {
+            label.set_text(&text);
+        }
+        badge_sync.show_all();
+        Continue(true)
+    });
+
     // Create wake overlay (starts HIDDEN, shown only when screen is off)
-    let wake = create_wake_overlay(&window_rc, last_activity, screen_is_off);
+    let win = window_rc.clone();
+    let wake = create_wake_overlay(last_activity, screen_is_off, move || {
+        win.show();
+        win.present();
+        let win_hide = win.clone();
+        timeout_add_local(Duration::from_secs(5), move || {
+            win_hide.hide();
+            Continue(false)
+        });
+    });
     let wake_rc = Rc::new(wake);
     
     MoteOverlay {
         control_window: window_rc,
         trigger_window: trigger_rc,
         wake_overlay: wake_rc,
+        keep_awake_badge: badge,
     }
 }
 
+/// Small "Awake" marker in the top-right corner, above the RDP session.
+/// Starts hidden; input passes through to the session around it.
+fn create_keep_awake_badge() -> Window {
+    let badge = Window::new(WindowType::Toplevel);
+    badge.set_decorated(false);
+    badge.set_accept_focus(false);
+    badge.set_opacity(0.7);
+
+    gtk_layer_shell::init_for_window(&badge);
+    gtk_layer_shell::set_layer(&badge, Layer::Overlay);
+    gtk_layer_shell::set_anchor(&badge, Edge::Top, true);
+    gtk_layer_shell::set_anchor(&badge, Edge::Right, true);
+    gtk_layer_shell::set_margin(&badge, Edge::Top, 8);
+    gtk_layer_shell::set_margin(&badge, Edge::Right, 8);
+    gtk_layer_shell::set_exclusive_zone(&badge, 0);
+
+    let label = Label::new(Some("Awake"));
+    label.set_margin_top(4);
+    label.set_margin_bottom(4);
+    label.set_margin_start(8);
+    label.set_margin_end(8);
+    badge.add(&label);
+    badge
+}
+
+/// How long "Keep awake" lasts, from settings.toml (`None` = until turned off).
+fn keep_awake_expiry() -> Option<Duration> {
+    let mins = settings::load_settings().idle.keep_awake_mins;
+    (mins > 0).then(|| Duration::from_secs(mins * 60))
+}
+
 /// Fullscreen wake overlay - starts HIDDEN, shown only when screen is off
-/// Tap anywhere to wake screen, then overlay hides itself
+/// Tap anywhere to wake screen, then overlay hides itself and calls `on_wake`
 fn create_wake_overlay(
-    control_window: &Rc<Window>,
     last_activity: &Rc<Cell<Instant>>,
     screen_is_off: &Rc<Cell<bool>>,
+    on_wake: impl Fn() + 'static,
 ) -> Window {
     let wake = Window::new(WindowType::Toplevel);
     wake.set_decorated(false);
@@ -611,7 +1663,6 @@ fn create_wake_overlay(
     let spacer = DrawingArea::new();
     event_box.add(&spacer);
     
-    let win = control_window.clone();
     let wake_ref = wake.clone();
     let la = last_activity.clone();
     let sio = screen_is_off.clone();
@@ -621,13 +1672,7 @@ fn create_wake_overlay(
         wake_ref.hide(); // Hide wake overlay so RDP gets input again
         la.set(Instant::now());
         sio.set(false);
-        win.show();
-        win.present();
-        let win_hide = win.clone();
-        timeout_add_local(Duration::from_secs(5), move || {
-            win_hide.hide();
-            Continue(false)
-        });
+        on_wake();
         gtk::Inhibit(true) // Consume the event
     });
     
@@ -638,6 +1683,276 @@ fn create_wake_overlay(
     wake
 }
 
+/// Photo-frame screensaver: full-screen slideshow of `settings.photo_dir`,
+/// crossfading every `interval_secs`. Starts hidden; like the wake overlay,
+/// a tap hides it and wakes the screen without reaching the session.
+fn create_photo_frame(
+    settings: &ScreensaverSettings,
+    last_activity: &Rc<Cell<Instant>>,
+    screen_is_off: &Rc<Cell<bool>>,
+) -> Window {
+    let frame = Window::new(WindowType::Toplevel);
+    frame.set_decorated(false);
+    frame.set_accept_focus(false);
+
+    let black = RGBA::new(0.0, 0.0, 0.0, 1.0);
+    unsafe {
+        gtk::ffi::gtk_widget_override_background_color(
+            frame.upcast_ref::<gtk::Widget>().to_glib_none().0,
+            gtk::StateFlags::NORMAL.bits(),
+            black.to_glib_none().0 as *const _,
+        );
+    }
+
+    gtk_layer_shell::init_for_window(&frame);
+    gtk_layer_shell::set_layer(&frame, Layer::Overlay);
+    gtk_layer_shell::set_anchor(&frame, Edge::Top, true);
+    gtk_layer_shell::set_anchor(&frame, Edge::Bottom, true);
+    gtk_layer_shell::set_anchor(&frame, Edge::Left, true);
+    gtk_layer_shell::set_anchor(&frame, Edge::Right, true);
+    gtk_layer_shell::set_exclusive_zone(&frame, -1);
+
+    // Two pages so each change crossfades from the previous photo
+    let stack = gtk::Stack::new();
+    stack.set_transition_type(gtk::StackTransitionType::Crossfade);
+    stack.set_transition_duration(1000);
+    let pages = [Image::new(), Image::new()];
+    stack.add_named(&pages[0], "a");
+    stack.add_named(&pages[1], "b");
+
+    let event_box = EventBox::new();
+    event_box.set_events(EventMask::TOUCH_MASK | EventMask::BUTTON_PRESS_MASK);
+    event_box.add(&stack);
+    let frame_ref = frame.clone();
+    let la = last_activity.clone();
+    let sio = screen_is_off.clone();
+    event_box.connect_button_press_event(move |_, _| {
+        log("Photo frame tapped - waking screen");
+        frame_ref.hide();
+        la.set(Instant::now());
+        sio.set(false);
+        wake_screen();
+        gtk::Inhibit(true)
+    });
+    frame.add(&event_box);
+
+    // Photos are listed again each time the frame comes up, so new ones
+    // show without a restart
+    let photo_dir = settings.photo_dir();
+    let photos: Rc<RefCell<Vec<std::path::PathBuf>>> = Rc::new(RefCell::new(Vec::new()));
+    let next = Rc::new(Cell::new(0usize));
+    // Camera photos take a while to decode; that happens off the main loop,
+    // one at a time
+    let loading = Rc::new(Cell::new(false));
+    let show_next = {
+        let photos = photos.clone();
+        let next = next.clone();
+        let stack = stack.clone();
+        let frame = frame.clone();
+        let pages = Rc::new(pages);
+        move || {
+            let photos = photos.borrow();
+            if photos.is_empty() || loading.get() {
+                return;
+            }
+            let i = next.get() % photos.len();
+            next.set(i + 1);
+            let path = photos[i].clone();
+            let stream = match gtk::gio::File::for_path(&path).read(gtk::gio::Cancellable::NONE) {
+                Ok(stream) => stream,
+                Err(e) => return log(&format!("Cannot show {}: {}", path.display(), e)),
+            };
+            let (width, height) = screen_size(&frame);
+            loading.set(true);
+            let loading = loading.clone();
+            let stack = stack.clone();
+            let pages = pages.clone();
+            gtk::gdk_pixbuf::Pixbuf::from_stream_at_scale_async(
+                &stream,
+                width,
+                height,
+                true,
+                gtk::gio::Cancellable::NONE,
+                move |result| {
+                    loading.set(false);
+                    match result {
+                        Ok(pixbuf) => {
+                            let page = if stack.visible_child_name().as_deref() == Some("a") { "b" } else { "a" };
+                            pages[usize::from(page == "b")].set_from_pixbuf(Some(&pixbuf));
+                            stack.set_visible_child_name(page);
+                        }
+                        Err(e) => log(&format!("Cannot show {}: {}", path.display(), e)),
+                    }
+                },
+            );
+        }
+    };
+    let show_next = Rc::new(show_next);
+    let on_show = show_next.clone();
+    frame.connect_show(move |_| {
+        *photos.borrow_mut() = screensaver::list_photos(&photo_dir);
+        log(&format!("Photo frame: {} photo(s) in {}", photos.borrow().len(), photo_dir.display()));
+        next.set(0);
+        on_show();
+    });
+    let frame_ref = frame.clone();
+    timeout_add_local(Duration::from_secs(settings.interval_secs.max(1)), move || {
+        if frame_ref.is_visible() {
+            show_next();
+        }
+        Continue(true)
+    });
+
+    frame.show_all();
+    frame.hide();
+    frame
+}
+
+/// Size of the monitor `widget` is on (the first one before it is shown),
+/// in application pixels.
+fn screen_size(widget: &impl IsA<gtk::Widget>) -> (i32, i32) {
+    let display = widget.display();
+    let monitor = widget.window().and_then(|w| display.monitor_at_window(&w)).or_else(|| display.monitor(0));
+    match monitor {
+        Some(m) => (m.geometry().width(), m.geometry().height()),
+        None => (800, 480),
+    }
+}
+
+/// Clock screensaver: time and date on black, optionally whether each
+/// profile's host answers, wandering a little every minute against burn-in.
+/// Starts hidden; a tap hides it and wakes the screen like the wake overlay.
+fn create_clock_screensaver(
+    settings: &ScreensaverSettings,
+    last_activity: &Rc<Cell<Instant>>,
+    screen_is_off: &Rc<Cell<bool>>,
+) -> Window {
+    const DRIFT_RANGE: i32 = 24;
+    const HOST_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+    let clock = Window::new(WindowType::Toplevel);
+    clock.set_decorated(false);
+    clock.set_accept_focus(false);
+
+    let black = RGBA::new(0.0, 0.0, 0.0, 1.0);
+    unsafe {
+        gtk::ffi::gtk_widget_override_background_color(
+            clock.upcast_ref::<gtk::Widget>().to_glib_none().0,
+            gtk::StateFlags::NORMAL.bits(),
+            black.to_glib_none().0 as *const _,
+        );
+    }
+
+    gtk_layer_shell::init_for_window(&clock);
+    gtk_layer_shell::set_layer(&clock, Layer::Overlay);
+    gtk_layer_shell::set_anchor(&clock, Edge::Top, true);
+    gtk_layer_shell::set_anchor(&clock, Edge::Bottom, true);
+    gtk_layer_shell::set_anchor(&clock, Edge::Left, true);
+    gtk_layer_shell::set_anchor(&clock, Edge::Right, true);
+    gtk_layer_shell::set_exclusive_zone(&clock, -1);
+
+    let box_ = GtkBox::new(Orientation::Vertical, 8);
+    box_.set_halign(gtk::Align::Center);
+    box_.set_valign(gtk::Align::Center);
+    let time = Label::new(None);
+    let date = Label::new(None);
+    let hosts = Label::new(None);
+    box_.pack_start(&time, false, false, 0);
+    box_.pack_start(&date, false, false, 0);
+    box_.pack_start(&hosts, false, false, 0);
+
+    let event_box = EventBox::new();
+    event_box.set_events(EventMask::TOUCH_MASK | EventMask::BUTTON_PRESS_MASK);
+    event_box.add(&box_);
+    let clock_ref = clock.clone();
+    let la = last_activity.clone();
+    let sio = screen_is_off.clone();
+    event_box.connect_button_press_event(move |_, _| {
+        log("Clock tapped - waking screen");
+        clock_ref.hide();
+        la.set(Instant::now());
+        sio.set(false);
+        wake_screen();
+        gtk::Inhibit(true)
+    });
+    clock.add(&event_box);
+
+    // Host checks run on a thread while the clock is up; the results are
+    // picked up by the tick below. One round at a time, so unreachable
+    // hosts cannot pile threads up.
+    let profiles = if settings.show_hosts { profiles::load_profiles() } else { Vec::new() };
+    let status: std::sync::Arc<std::sync::Mutex<Vec<(String, bool)>>> = Default::default();
+    let probing = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    // Bumped on hide, so a round still running then is not shown next time
+    let showing = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let mut last_check: Option<Instant> = None;
+    let clock_ref = clock.clone();
+    let update = move || {
+        if !clock_ref.is_visible() {
+            if last_check.take().is_some() {
+                showing.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                status.lock().unwrap_or_else(|e| e.into_inner()).clear();
+                hosts.set_markup("");
+            }
+            return;
+        }
+        let now = chrono::Local::now();
+        time.set_markup(&format!("<span font='Sans 72' foreground='#bbbbbb'>{}</span>", now.format("%H:%M")));
+        date.set_markup(&format!("<span font='Sans 20' foreground='#888888'>{}</span>", now.format("%A, %-d %B")));
+        let (dx, dy) = screensaver::drift_offset(now.timestamp() as u64 / 60, DRIFT_RANGE);
+        box_.set_margin_start(DRIFT_RANGE + dx);
+        box_.set_margin_end(DRIFT_RANGE - dx);
+        box_.set_margin_top(DRIFT_RANGE + dy);
+        box_.set_margin_bottom(DRIFT_RANGE - dy);
+
+        if profiles.is_empty() {
+            return;
+        }
+        let due = last_check.is_none_or(|t| t.elapsed() >= HOST_CHECK_INTERVAL);
+        if due && !probing.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            last_check = Some(Instant::now());
+            let targets: Vec<(String, String, u16)> =
+                profiles.iter().map(|p| (p.name.clone(), p.host.clone(), p.port)).collect();
+            let status = status.clone();
+            let probing = probing.clone();
+            let showing = showing.clone();
+            let started = showing.load(std::sync::atomic::Ordering::SeqCst);
+            std::thread::spawn(move || {
+                let results = targets
+                    .into_iter()
+                    .map(|(name, host, port)| (name, screensaver::host_reachable(&host, port, Duration::from_secs(2))))
+                    .collect();
+                let mut status = status.lock().unwrap_or_else(|e| e.into_inner());
+                if showing.load(std::sync::atomic::Ordering::SeqCst) == started {
+                    *status = results;
+                }
+                drop(status);
+                probing.store(false, std::sync::atomic::Ordering::SeqCst);
+            });
+        }
+        let lines: Vec<String> = status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, up)| {
+                let (dot, color) = if *up { ("●", "#5fa85f") } else { ("○", "#777777") };
+                format!("<span foreground='{}'>{}</span> {}", color, dot, glib::markup_escape_text(name))
+            })
+            .collect();
+        hosts.set_markup(&format!("<span font='Sans 14' foreground='#888888'>{}</span>", lines.join("    ")));
+    };
+    // Realize hidden first, so the show handler only runs for real
+    clock.show_all();
+    clock.hide();
+    let update = Rc::new(RefCell::new(update));
+    let on_show = update.clone();
+    clock.connect_show(move |_| (on_show.borrow_mut())());
+    timeout_add_local(Duration::from_secs(1), move || {
+        (update.borrow_mut())();
+        Continue(true)
+    });
+    clock
+}
+
 fn create_top_trigger(
     control_window: &Rc<Window>,
     last_activity: &Rc<Cell<Instant>>,
@@ -769,89 +2084,122 @@ fn find_sway_socket() -> Option<String> {
     None
 }
 
-fn run_swaymsg(args: &[&str]) -> bool {
-    let socket = find_sway_socket();
-    let mut cmd = Command::new("swaymsg");
-    if let Some(ref sock) = socket {
-        cmd.arg("-s").arg(sock);
-    }
-    cmd.args(args);
-    
-    match cmd.output() {
-        Ok(output) => {
-            if output.status.success() {
-                true
-            } else {
-                log(&format!("swaymsg {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr)));
-                false
-            }
+fn sway_connect() -> Result<sway_ipc::Connection, sway_ipc::Error> {
+    let socket = find_sway_socket().ok_or(sway_ipc::Error::NoSocket)?;
+    sway_ipc::Connection::connect(Path::new(&socket))
+}
+
+/// Sway container id of the window belonging to process `pid`.
+fn find_window_by_pid(pid: u32) -> Option<i64> {
+    let tree = sway_connect().and_then(|mut c| c.get_tree()).ok()?;
+    tree.find(&|n| n.pid == Some(pid)).map(|n| n.id)
+}
+
+/// Switch every active output's DPMS state over sway IPC.
+fn sway_set_power(on: bool) -> bool {
+    let state = if on { "on" } else { "off" };
+    let result = sway_connect().and_then(|mut c| {
+        let outputs = c.get_outputs()?;
+        let mut names = Vec::new();
+        for output in outputs.iter().filter(|o| o.active) {
+            c.run_command(&format!("output \"{}\" dpms {}", output.name, state))?;
+            names.push(output.name.as_str());
+        }
+        Ok(names.join(", "))
+    });
+    match result {
+        Ok(names) if !names.is_empty() => {
+            log(&format!("DPMS {} on {}", state, names));
+            true
+        }
+        Ok(_) => {
+            log("sway reports no active outputs");
+            false
         }
         Err(e) => {
-            log(&format!("swaymsg not found: {}", e));
+            log(&format!("sway DPMS {}: {}", state, e));
             false
         }
     }
 }
 
-fn find_backlight() -> Option<String> {
-    if let Ok(entries) = std::fs::read_dir("/sys/class/backlight") {
-        for entry in entries.flatten() {
-            let path = entry.path().join("brightness");
-            if path.exists() {
-                return Some(path.to_string_lossy().to_string());
-            }
+/// Run a sway command over IPC, logging why it failed.
+fn sway_command(command: &str) -> bool {
+    match sway_connect().and_then(|mut c| c.run_command(command)) {
+        Ok(()) => true,
+        Err(e) => {
+            log(&format!("sway \"{}\": {}", command, e));
+            false
+        }
+    }
+}
+
+/// Outputs on/off: sway IPC when running under sway, otherwise (or when
+/// the socket found is stale) wlr-output-power-management (labwc,
+/// Wayfire, ...).
+fn set_screen_power(on: bool) -> bool {
+    if find_sway_socket().is_some() && sway_set_power(on) {
+        return true;
+    }
+    match output_power::set_power(on) {
+        Ok(n) => {
+            log(&format!("{} output(s) switched {} via wlr-output-power-management", n, if on { "on" } else { "off" }));
+            true
+        }
+        Err(e) => {
+            log(&format!("wlr-output-power-management: {}", e));
+            false
         }
     }
-    None
 }
 
 fn turn_screen_off() {
     log("Turning screen off");
     // Try backlight control first (works on Pi DSI displays)
-    if let Some(backlight) = find_backlight() {
-        if std::fs::write(&backlight, "0").is_ok() {
-            log("Screen turned off via backlight");
-            return;
+    if let Some(backlight) = Backlight::find() {
+        match backlight.off() {
+            Ok(()) => {
+                log(&format!("Screen turned off via backlight {}", backlight.name()));
+                return;
+            }
+            Err(e) => log(&format!("Backlight {} off failed: {}", backlight.name(), e)),
         }
     }
-    // Fallback to swaymsg DPMS
-    if run_swaymsg(&["output", "*", "dpms", "off"]) {
-        log("Screen turned off via swaymsg");
-        return;
+    if !set_screen_power(false) {
+        log("WARNING: Failed to turn screen off");
     }
-    // Fallback to xset (X11)
-    if let Ok(output) = Command::new("xset").args(["dpms", "force", "off"]).output() {
-        if output.status.success() {
-            log("Screen turned off via xset");
-            return;
-        }
-    }
-    log("WARNING: Failed to turn screen off");
 }
 
 fn wake_screen() {
     log("Waking screen");
     // Try backlight control first (works on Pi DSI displays)
-    if let Some(backlight) = find_backlight() {
-        if std::fs::write(&backlight, "255").is_ok() {
-            log("Screen woken via backlight");
-            return;
+    if let Some(backlight) = Backlight::find() {
+        match backlight.on() {
+            Ok(()) => {
+                log(&format!("Screen woken via backlight {}", backlight.name()));
+                return;
+            }
+            Err(e) => log(&format!("Backlight {} on failed: {}", backlight.name(), e)),
         }
     }
-    // Fallback to swaymsg DPMS
-    if run_swaymsg(&["output", "*", "dpms", "on"]) {
-        log("Screen woken via swaymsg");
-        return;
+    if !set_screen_power(true) {
+        log("WARNING: Failed to wake screen");
     }
-    // Fallback to xset (X11)
-    if let Ok(output) = Command::new("xset").args(["dpms", "force", "on"]).output() {
-        if output.status.success() {
-            log("Screen woken via xset");
-            return;
-        }
-    }
-    // Fallback: simulate a key press to wake
-    Command::new("xdotool").args(["key", "Shift"]).output().ok();
-    log("Screen wake attempted via xdotool");
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rdp_command_has_host() {
+        let cmd = rdp_command_line(&Profile::new("Mote", "10.1.1.3", "tv"), None);
+        assert!(cmd.contains("/v:10.1.1.3"), "missing /v:host: {}", cmd);
+        assert!(cmd.contains("sdl-freerdp3"), "must use sdl-freerdp3: {}", cmd);
+        assert!(cmd.contains("/w:800"), "missing width: {}", cmd);
+        assert!(cmd.contains("/h:480"), "missing height: {}", cmd);
+        assert!(cmd.contains("/f"), "missing fullscreen: {}", cmd);
+        assert!(cmd.contains("/u:tv"), "missing user: {}", cmd);
+        assert!(cmd.contains("+multitouch"), "missing multitouch: {}", cmd);
+    }
+}
@@ -0,0 +1,289 @@
+//! Turning a failed sdl-freerdp3 run into something a person can act on.
+//!
+//! FreeRDP reports failures two ways: its exit code (the `SDL_EXIT_*` /
+//! `XF_EXIT_*` values) and `ERRCONNECT_*` / `ERRINFO_*` names plus socket
+//! errors in its log. `classify` looks at both, most specific first.
+
+/// What went wrong, broadly enough to suggest a fix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Host name could not be resolved.
+    Dns,
+    /// The host answered but nothing listens on the RDP port.
+    Refused,
+    /// No answer: host down, wrong address, or firewall.
+    Unreachable,
+    /// TLS handshake or certificate verification failed.
+    Certificate,
+    /// NLA rejected the user name or password.
+    Authentication,
+    /// The account is locked out, disabled or expired.
+    AccountLocked,
+    /// Another client took the session over, or an administrator ended it.
+    TakenOver,
+    /// The server ended an established session.
+    ServerDisconnect,
+    Unknown,
+}
+
+// FreeRDP client exit codes (client/SDL/sdl_types.hpp, client/X11/xf_client.h)
+const EXIT_DISCONNECT: i32 = 1;
+const EXIT_IDLE_TIMEOUT: i32 = 3;
+const EXIT_LOGON_TIMEOUT: i32 = 4;
+const EXIT_CONN_REPLACED: i32 = 5;
+const EXIT_CONN_DENIED: i32 = 7;
+const EXIT_AUTH_FAILURE: i32 = 132;
+const EXIT_LOGON_FAILURE: i32 = 134;
+const EXIT_ACCOUNT_LOCKED_OUT: i32 = 135;
+const EXIT_DNS_ERROR: i32 = 139;
+const EXIT_DNS_NAME_NOT_FOUND: i32 = 140;
+const EXIT_TLS_CONNECT_FAILED: i32 = 143;
+const EXIT_ACCOUNT_DISABLED: i32 = 151;
+const EXIT_WRONG_PASSWORD: i32 = 154;
+const EXIT_ACCOUNT_EXPIRED: i32 = 157;
+const EXIT_NO_OR_MISSING_CREDENTIALS: i32 = 159;
+
+/// Log patterns per kind, checked in this order.
+const PATTERNS: &[(FailureKind, &[&str])] = &[
+    (
+        FailureKind::AccountLocked,
+        &[
+            "ACCOUNT_LOCKED_OUT",
+            "ACCOUNT_DISABLED",
+            "ACCOUNT_EXPIRED",
+            "ACCOUNT_RESTRICTION",
+            "PASSWORD_EXPIRED",
+            "PASSWORD_MUST_CHANGE",
+        ],
+    ),
+    (
+        FailureKind::Authentication,
+        &[
+            "LOGON_FAILURE",
+            "AUTHENTICATION_FAILED",
+            "WRONG_PASSWORD",
+            "SEC_E_LOGON_DENIED",
+            "NO_OR_MISSING_CREDENTIALS",
+            "ACCESS_DENIED",
+        ],
+    ),
+    (
+        FailureKind::Certificate,
+        &[
+            "CERTIFICATE NAME MISMATCH",
+            "HOST IDENTIFICATION HAS CHANGED",
+            "tls_verify_certificate",
+            "certificate verification",
+            "TLS_CONNECT_FAILED",
+        ],
+    ),
+    (
+        FailureKind::Dns,
+        &[
+            "DNS_NAME_NOT_FOUND",
+            "DNS_ERROR",
+            "Name or service not known",
+            "Temporary failure in name resolution",
+            "getaddrinfo",
+        ],
+    ),
+    (FailureKind::Refused, &["Connection refused", "ECONNREFUSED"]),
+    (
+        FailureKind::Unreachable,
+        &[
+            "timed out",
+            "ETIMEDOUT",
+            "No route to host",
+            "Network is unreachable",
+            "Host is unreachable",
+        ],
+    ),
+    (
+        FailureKind::TakenOver,
+        &["ERRINFO_DISCONNECTED_BY_OTHER_CONNECTION", "ERRINFO_RPC_INITIATED_DISCONNECT", "ERRINFO_RPC_INITIATED_LOGOFF"],
+    ),
+    (
+        FailureKind::ServerDisconnect,
+        &[
+            "ERRINFO_SERVER_",
+            "ERRINFO_IDLE_TIMEOUT",
+            "ERRINFO_LOGON_TIMEOUT",
+            "ERRCONNECT_CONNECT_CANCELLED",
+            "Connection reset by peer",
+        ],
+    ),
+];
+
+/// A classified failure and the FreeRDP lines it was derived from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub details: Vec<String>,
+}
+
+impl Failure {
+    /// Message, suggested fix and the quoted FreeRDP lines, for an error dialog.
+    pub fn describe(&self, target: &str) -> String {
+        let mut text = format!("{}\n\n{}", self.kind.message(target), self.kind.suggestion());
+        if !self.details.is_empty() {
+            text.push_str("\n\n");
+            text.push_str(&self.details.join("\n"));
+        }
+        text
+    }
+}
+
+/// Classify a failed run from its exit code and output lines.
+pub fn classify(exit_code: Option<i32>, lines: &[String]) -> FailureKind {
+    for (kind, patterns) in PATTERNS {
+        if lines.iter().any(|l| patterns.iter().any(|p| l.contains(p))) {
+            return *kind;
+        }
+    }
+    match exit_code {
+        Some(EXIT_ACCOUNT_LOCKED_OUT | EXIT_ACCOUNT_DISABLED | EXIT_ACCOUNT_EXPIRED) => FailureKind::AccountLocked,
+        Some(EXIT_AUTH_FAILURE | EXIT_LOGON_FAILURE | EXIT_WRONG_PASSWORD | EXIT_NO_OR_MISSING_CREDENTIALS) => {
+            FailureKind::Authentication
+        }
+        Some(EXIT_TLS_CONNECT_FAILED) => FailureKind::Certificate,
+        Some(EXIT_DNS_ERROR | EXIT_DNS_NAME_NOT_FOUND) => FailureKind::Dns,
+        Some(EXIT_CONN_REPLACED) => FailureKind::TakenOver,
+        Some(EXIT_DISCONNECT | EXIT_IDLE_TIMEOUT | EXIT_LOGON_TIMEOUT | EXIT_CONN_DENIED) => {
+            FailureKind::ServerDisconnect
+        }
+        _ => FailureKind::Unknown,
+    }
+}
+
+impl FailureKind {
+    pub fn title(self) -> &'static str {
+        match self {
+            FailureKind::Dns => "Host not found",
+            FailureKind::Refused => "Connection refused",
+            FailureKind::Unreachable => "Host unreachable",
+            FailureKind::Certificate => "Certificate problem",
+            FailureKind::Authentication => "Login failed",
+            FailureKind::AccountLocked => "Account locked",
+            FailureKind::TakenOver => "Session taken over",
+            FailureKind::ServerDisconnect => "Disconnected by server",
+            FailureKind::Unknown => "Connection failed",
+        }
+    }
+
+    /// What happened, for `target` ("host:port").
+    pub fn message(self, target: &str) -> String {
+        match self {
+            FailureKind::Dns => format!("The name in {} could not be resolved.", target),
+            FailureKind::Refused => format!("{} is up but refused the RDP connection.", target),
+            FailureKind::Unreachable => format!("{} did not answer.", target),
+            FailureKind::Certificate => format!("The secure (TLS) connection to {} could not be verified.", target),
+            FailureKind::Authentication => format!("{} rejected the user name or password.", target),
+            FailureKind::AccountLocked => format!("The account on {} is locked, disabled or expired.", target),
+            FailureKind::TakenOver => format!("The session on {} was taken over or ended by an administrator.", target),
+            FailureKind::ServerDisconnect => format!("{} ended the session.", target),
+            FailureKind::Unknown => format!("Could not connect to KRDP at {}.", target),
+        }
+    }
+
+    pub fn suggestion(self) -> &'static str {
+        match self {
+            FailureKind::Dns => "Check the host name in profiles.toml, or use the host's IP address.",
+            FailureKind::Refused => "Make sure KRDP is running on the host and listening on this port.",
+            FailureKind::Unreachable => "Check that the host is powered on and on the same network, and that no firewall blocks the port.",
+            FailureKind::Certificate => "If the host's certificate was regenerated, remove its entry from known_hosts.toml and accept the new one.",
+            FailureKind::Authentication => "Update the stored password: desktop-icons credential rotate <id>",
+            FailureKind::AccountLocked => "Unlock or re-enable the account on the host, then try again.",
+            FailureKind::TakenOver => "Mote does not take the session back on its own. Connect again once it is free.",
+            FailureKind::ServerDisconnect => "The session was closed on the host, or the host restarted.",
+            FailureKind::Unknown => "Check host, port, and credentials.",
+        }
+    }
+
+    /// Whether reconnecting automatically can help. Retrying bad credentials
+    /// would only lock the account, and reconnecting after a takeover would
+    /// steal the session back from whoever has it now.
+    pub fn retryable(self) -> bool {
+        !matches!(
+            self,
+            FailureKind::Certificate
+                | FailureKind::Authentication
+                | FailureKind::AccountLocked
+                | FailureKind::TakenOver
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(text: &str) -> Vec<String> {
+        text.lines().map(|l| l.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn classifies_captured_freerdp_output() {
+        let cases = [
+            (
+                FailureKind::Dns,
+                "[12:41:07:301] [1302:1303] [ERROR][com.freerdp.core] - [freerdp_tcp_resolve_host]: getaddrinfo (Name or service not known)
+                 [12:41:07:301] [1302:1303] [ERROR][com.freerdp.core] - [freerdp_set_last_error_ex]: ERRCONNECT_DNS_NAME_NOT_FOUND [0x00020005]",
+            ),
+            (
+                FailureKind::Refused,
+                "[09:12:44:010] [811:812] [ERROR][com.freerdp.core] - [freerdp_tcp_connect]: connect to 10.1.1.3:3389 failed: Connection refused
+                 [09:12:44:010] [811:812] [ERROR][com.freerdp.core] - [freerdp_set_last_error_ex]: ERRCONNECT_CONNECT_FAILED [0x00020006]",
+            ),
+            (
+                FailureKind::Unreachable,
+                "[09:13:15:220] [811:812] [ERROR][com.freerdp.core] - [freerdp_tcp_connect_timeout]: connect to 10.1.1.99:3389 timed out
+                 [09:13:15:220] [811:812] [ERROR][com.freerdp.core] - [freerdp_set_last_error_ex]: ERRCONNECT_CONNECT_TRANSPORT_FAILED [0x0002000D]",
+            ),
+            (
+                FailureKind::Certificate,
+                "[20:01:02:003] [900:901] [ERROR][com.freerdp.crypto] - [tls_verify_certificate]: certificate fingerprint does not match
+                 [20:01:02:004] [900:901] [ERROR][com.freerdp.core] - [transport_connect_tls]: ERRCONNECT_TLS_CONNECT_FAILED [0x00020008]",
+            ),
+            (
+                FailureKind::Authentication,
+                "[18:22:10:551] [700:701] [ERROR][com.freerdp.core.nla] - [nla_recv_pdu]: ERRCONNECT_LOGON_FAILURE [0x00020014]
+                 [18:22:10:551] [700:701] [ERROR][com.freerdp.core.rdp] - [rdp_recv_callback_int]: CONNECTION_STATE_NLA - nla_recv_pdu() fail",
+            ),
+            (
+                FailureKind::AccountLocked,
+                "[18:25:00:100] [702:703] [ERROR][com.freerdp.core.nla] - [nla_recv_pdu]: ERRCONNECT_ACCOUNT_LOCKED_OUT [0x00020017]
+                 [18:25:00:100] [702:703] [ERROR][com.freerdp.core] - [freerdp_set_last_error_ex]: ERRCONNECT_LOGON_FAILURE [0x00020014]",
+            ),
+            (
+                FailureKind::TakenOver,
+                "[21:00:00:000] [705:706] [INFO][com.freerdp.core] - [rdp_print_errinfo]: ERRINFO_RPC_INITIATED_DISCONNECT (0x00000001):The disconnection was initiated by an administrative tool on the server in another session.",
+            ),
+            (
+                FailureKind::TakenOver,
+                "[21:05:00:000] [705:706] [INFO][com.freerdp.core] - [rdp_print_errinfo]: ERRINFO_DISCONNECTED_BY_OTHER_CONNECTION (0x00000005):Another user connected to the server, forcing the disconnection of the current connection.",
+            ),
+            (
+                FailureKind::ServerDisconnect,
+                "[21:10:00:000] [705:706] [ERROR][com.freerdp.core] - [transport_read_layer]: BIO_read returned a system error 104: Connection reset by peer",
+            ),
+            (FailureKind::Unknown, "[21:00:00:000] [705:706] [INFO][com.freerdp.client] - something else"),
+        ];
+        for (expected, text) in cases {
+            let kind = classify(Some(131), &sample(text));
+            assert_eq!(kind, expected, "misclassified:\n{}", text);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_exit_code() {
+        assert_eq!(classify(Some(EXIT_WRONG_PASSWORD), &[]), FailureKind::Authentication);
+        assert_eq!(classify(Some(EXIT_DNS_NAME_NOT_FOUND), &[]), FailureKind::Dns);
+        assert_eq!(classify(Some(EXIT_CONN_REPLACED), &[]), FailureKind::TakenOver);
+        assert_eq!(classify(Some(EXIT_DISCONNECT), &[]), FailureKind::ServerDisconnect);
+        assert_eq!(classify(None, &[]), FailureKind::Unknown);
+        assert!(!FailureKind::Authentication.retryable());
+        assert!(!FailureKind::TakenOver.retryable(), "must not steal the session back");
+        assert!(FailureKind::ServerDisconnect.retryable());
+        assert!(FailureKind::Unreachable.retryable());
+    }
+}
@@ -0,0 +1,235 @@
+//! Launching the sdl-freerdp3 client.
+//!
+//! The password never goes on the command line, where any local user could
+//! read it from `/proc/*/cmdline` or `ps`. `/from-stdin:force` makes FreeRDP
+//! prompt on stdin before connecting, and we write it into the pipe. FreeRDP
+//! (`client_cli_authenticate_raw`) prompts for every credential field that is
+//! still unset, in the order user name, domain, password, one line each. So
+//! `/u:` and `/d:` are always given (the domain empty unless a `/d:` in
+//! `freerdp_options` overrides it) and the password is the only line it reads.
+//!
+//! stdout and stderr are drained continuously by reader threads into an
+//! `OutputLog` ring buffer (and the debug log), so a chatty session can never
+//! stall on a full pipe and the last lines are at hand whenever it fails.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::certs;
+use crate::log;
+use crate::profiles::Profile;
+
+pub const FREERDP_BIN: &str = "sdl-freerdp3";
+
+/// Lines of FreeRDP output kept in memory.
+pub const OUTPUT_LOG_LINES: usize = 200;
+
+/// Bounded buffer of the most recent FreeRDP output lines, shared between the
+/// reader threads and whoever reports failures.
+#[derive(Clone)]
+pub struct OutputLog {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl OutputLog {
+    pub fn new(capacity: usize) -> Self {
+        OutputLog {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, line: &str) {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    /// The last `n` warning/error lines, tidied for display. Falls back to
+    /// the last `n` lines of any kind when FreeRDP logged no levels.
+    pub fn relevant(&self, n: usize) -> Vec<String> {
+        let lines = self.lines();
+        let mut picked: Vec<String> = lines
+            .iter()
+            .rev()
+            .filter(|l| ["[ERROR]", "[FATAL]", "[WARN]"].iter().any(|lvl| l.contains(lvl)))
+            .take(n)
+            .map(|l| tidy_line(l))
+            .collect();
+        if picked.is_empty() {
+            picked = lines.iter().rev().filter(|l| !l.trim().is_empty()).take(n).map(|l| tidy_line(l)).collect();
+        }
+        picked.reverse();
+        picked
+    }
+}
+
+/// `[12:00:01:234] [42:43] [ERROR][com.freerdp.core] - msg` -> `ERROR: msg`
+fn tidy_line(line: &str) -> String {
+    let level = ["ERROR", "FATAL", "WARN"].into_iter().find(|lvl| line.contains(&format!("[{}]", lvl)));
+    match (level, line.split_once("] - ")) {
+        (Some(level), Some((_, msg))) => format!("{}: {}", level, msg.trim()),
+        (None, Some((_, msg))) => msg.trim().to_string(),
+        _ => line.trim().to_string(),
+    }
+}
+
+/// A running sdl-freerdp3 and the threads draining its output.
+pub struct Client {
+    pub child: Child,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl Client {
+    /// After the process exited: wait until its remaining output is in the log.
+    pub fn finish_output(&mut self) {
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Copy `stream` line by line into `output` and the debug log until EOF.
+fn capture<R: Read + Send + 'static>(stream: R, name: &'static str, output: OutputLog) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else { break };
+            log(&format!("[{}] freerdp {}: {}", chrono::Local::now().format("%H:%M:%S%.3f"), name, line));
+            output.push(&line);
+        }
+    })
+}
+
+/// Full sdl-freerdp3 argv (minus the program) for `profile`, trusting only
+/// the certificate with the pinned `fingerprint`.
+pub fn connect_args(profile: &Profile, fingerprint: Option<&str>) -> Vec<String> {
+    let mut args = profile.freerdp_args();
+    // Right after /u:, so a /d: among the profile's options comes later and wins
+    args.insert(1, "/d:".to_string());
+    args.insert(2, "/from-stdin:force".to_string());
+    args.insert(3, certs::freerdp_cert_arg(fingerprint));
+    args
+}
+
+/// Start sdl-freerdp3 for `profile`, passing `password` over stdin and
+/// capturing its output into `output`.
+pub fn spawn(profile: &Profile, fingerprint: &str, password: &str, output: &OutputLog) -> io::Result<Client> {
+    let mut cmd = Command::new(FREERDP_BIN);
+    cmd.args(connect_args(profile, Some(fingerprint)));
+    start(cmd, password, output)
+}
+
+/// Run `cmd` as the client: `password` on stdin, output captured into
+/// `output`. The log is shared by every run of a session but only ever
+/// holds the current one, so failures are classified and quoted from it
+/// alone; the previous client's readers are done by then (see
+/// `Client::finish_output`).
+pub fn start(mut cmd: Command, password: &str, output: &OutputLog) -> io::Result<Client> {
+    output.clear();
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = spawn_with_password(cmd, password)?;
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(capture(stdout, "stdout", output.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(capture(stderr, "stderr", output.clone()));
+    }
+    Ok(Client { child, readers })
+}
+
+/// Exit codes for sessions that ended on purpose and must not be reconnected:
+/// success, logoff, and disconnect by user.
+pub fn is_intentional_exit(code: i32) -> bool {
+    matches!(code, 0 | 2 | 11)
+}
+
+/// Spawn `cmd` with a piped stdin, write `password` and a newline, then close
+/// the pipe so a second prompt (e.g. after a failed logon) sees EOF instead
+/// of hanging. A client that exits before reading it is returned as is; its
+/// exit status and output say why.
+fn spawn_with_password(mut cmd: Command, password: &str) -> io::Result<Child> {
+    let mut child = cmd.stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = writeln!(stdin, "{}", password).or_else(ignore_broken_pipe) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    }
+    Ok(child)
+}
+
+fn ignore_broken_pipe(e: io::Error) -> io::Result<()> {
+    match e.kind() {
+        io::ErrorKind::BrokenPipe => Ok(()),
+        _ => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_goes_over_stdin_not_argv() {
+        let secret = "s3cret-Pa55";
+        let profile = Profile::new("Mote", "10.1.1.3", "tv");
+        let args = connect_args(&profile, None);
+        assert!(args.iter().all(|a| !a.contains(secret)), "secret in argv: {:?}", args);
+        assert!(args.contains(&"/from-stdin:force".to_string()), "missing /from-stdin: {:?}", args);
+        assert!(args.contains(&"/d:".to_string()), "domain left for FreeRDP to prompt: {:?}", args);
+
+        // Stand-in client: prints its own cmdline, then prompts like FreeRDP
+        // for whichever of user name and domain argv left unset, then the
+        // password, and prints what it read
+        let script = r#"tr '\0\n' '  ' < /proc/$$/cmdline; echo
+            u=; d=
+            for a; do case $a in /u:*) u=1 ;; /d:*) d=1 ;; esac; done
+            [ -n "$u" ] || read user
+            [ -n "$d" ] || read domain
+            read pw; echo "stdin:$pw""#;
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script, "sh"])
+            .args(&args)
+            .stdout(Stdio::piped());
+        let out = spawn_with_password(cmd, secret)
+            .expect("spawn sh")
+            .wait_with_output()
+            .expect("wait sh");
+        let out = String::from_utf8_lossy(&out.stdout);
+        let mut lines = out.lines();
+        let cmdline = lines.next().unwrap_or_default();
+        assert!(cmdline.contains("/v:10.1.1.3"), "unexpected cmdline: {}", cmdline);
+        assert!(!cmdline.contains(secret), "secret visible in /proc cmdline: {}", cmdline);
+        assert_eq!(lines.next(), Some(format!("stdin:{}", secret).as_str()));
+    }
+
+    #[test]
+    fn output_log_is_bounded_and_picks_errors() {
+        let output = OutputLog::new(3);
+        output.push("[10:00:00:001] [1:2] [INFO][com.freerdp.client] - connecting");
+        output.push("[10:00:00:002] [1:2] [ERROR][com.freerdp.core] - transport_connect_tls:freerdp_set_last_error_ex ERRCONNECT_TLS_CONNECT_FAILED");
+        output.push("[10:00:00:003] [1:2] [INFO][com.freerdp.client] - retrying");
+        output.push("[10:00:00:004] [1:2] [INFO][com.freerdp.client] - bye");
+        assert_eq!(output.lines().len(), 3, "ring buffer over capacity: {:?}", output.lines());
+        assert_eq!(
+            output.relevant(5),
+            ["ERROR: transport_connect_tls:freerdp_set_last_error_ex ERRCONNECT_TLS_CONNECT_FAILED"]
+        );
+    }
+}
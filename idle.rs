@@ -0,0 +1,542 @@
+//! Idle stages: how far the device has gone down after a stretch without
+//! input (dimmed, screen off, disconnected), and `IdleTimer`, which decides
+//! once a second what the idle timer in desktop-icons.rs has to do about it.
+
+use std::time::{Duration, Instant};
+
+use crate::schedule::Scheduled;
+use crate::settings::IdleSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Active,
+    Dimmed,
+    Off,
+    Disconnected,
+}
+
+/// When each stage starts, measured from the last input. `None` skips it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdleStages {
+    pub dim_after: Option<Duration>,
+    pub dim_percent: u32,
+    pub off_after: Option<Duration>,
+    pub disconnect_after: Option<Duration>,
+}
+
+impl IdleStages {
+    pub fn from_settings(s: &IdleSettings) -> Self {
+        let after = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        IdleStages {
+            dim_after: after(s.dim_after_secs),
+            dim_percent: s.dim_percent.min(100),
+            off_after: after(s.off_after_secs),
+            disconnect_after: after(s.disconnect_after_mins * 60),
+        }
+    }
+
+    /// Stages for the home screen: same dimming, its own blank timeout and
+    /// nothing to disconnect.
+    pub fn home_screen(s: &IdleSettings) -> Self {
+        IdleStages {
+            off_after: (s.home_off_after_secs > 0).then(|| Duration::from_secs(s.home_off_after_secs)),
+            disconnect_after: None,
+            ..Self::from_settings(s)
+        }
+    }
+
+    /// The deepest stage reached after `idle` without input.
+    pub fn stage(&self, idle: Duration) -> Stage {
+        let reached = |after: Option<Duration>| after.is_some_and(|a| idle >= a);
+        if reached(self.disconnect_after) {
+            Stage::Disconnected
+        } else if reached(self.off_after) {
+            Stage::Off
+        } else if reached(self.dim_after) {
+            Stage::Dimmed
+        } else {
+            Stage::Active
+        }
+    }
+}
+
+/// "Keep awake": holds off every idle stage, until turned off or a deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepAwake {
+    #[default]
+    Off,
+    On,
+    Until(Instant),
+}
+
+impl KeepAwake {
+    /// Turn on from `now`, for `expiry` when given.
+    pub fn start(now: Instant, expiry: Option<Duration>) -> Self {
+        match expiry {
+            Some(e) => KeepAwake::Until(now + e),
+            None => KeepAwake::On,
+        }
+    }
+
+    pub fn active(&self, now: Instant) -> bool {
+        match self {
+            KeepAwake::Off => false,
+            KeepAwake::On => true,
+            KeepAwake::Until(deadline) => now < *deadline,
+        }
+    }
+
+    /// Time left before it turns itself off (`None` when it never does).
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        match self {
+            KeepAwake::Until(deadline) => Some(deadline.saturating_duration_since(now)),
+            _ => None,
+        }
+    }
+}
+
+/// What the idle timer knows at one tick.
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    pub now: Instant,
+    /// Last activity counted so far (overlay taps, earlier input).
+    pub last_activity: Instant,
+    /// Most recent real input, when an input monitor runs.
+    pub last_input: Option<Instant>,
+    pub keep_awake: KeepAwake,
+    /// Screen blanked, or a screensaver up instead.
+    pub screen_off: bool,
+    pub screensaver_up: bool,
+    pub scheduled: Scheduled,
+    /// The screen belongs to the home screen (session parked): only count
+    /// towards disconnecting.
+    pub background: bool,
+}
+
+/// Something the idle timer has to do, in the order given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    KeepAwakeExpired,
+    /// Light the screen again: input while blank, or (`scheduled`) the end
+    /// of screen-off hours.
+    Wake { scheduled: bool },
+    /// Back to the remembered brightness after dimming.
+    Restore,
+    Dim(u32),
+    /// Blank the screen, hiding any screensaver; the wake overlay goes up
+    /// unless any input wakes.
+    ScreenOff { scheduled: bool },
+    /// Start the screensaver (or blank when it has nothing to show).
+    Screensaver,
+    /// Take an ambient light reading.
+    AutoBrightness,
+    /// Hold brightness at the schedule's cap; `starts` on the first tick.
+    Cap { percent: u32, starts: bool },
+    /// The schedule's cap is over.
+    Uncap,
+    Disconnect,
+}
+
+/// Result of a tick: the updated activity and screen state, and the actions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub last_activity: Instant,
+    pub screen_off: bool,
+    pub actions: Vec<Action>,
+}
+
+/// The idle timer's decisions, apart from the windows and the backlight.
+#[derive(Debug, Clone)]
+pub struct IdleTimer {
+    stages: IdleStages,
+    /// How long a touch lights the screen during scheduled screen-off hours.
+    wake_for: Option<Duration>,
+    /// Input while blank wakes the screen (and reaches what is under it).
+    wake_on_input: bool,
+    screensaver: bool,
+    stage: Stage,
+    /// The Off stage came from screen-off hours.
+    scheduled_off: bool,
+    capped: bool,
+    /// `last_activity` as last returned, and the last tap or input behind
+    /// it (Keep awake moves the first along, not the second).
+    seen: Option<(Instant, Instant)>,
+}
+
+impl IdleTimer {
+    pub fn new(stages: IdleStages, wake_for: Option<Duration>, wake_on_input: bool, screensaver: bool) -> Self {
+        IdleTimer {
+            stages,
+            wake_for,
+            wake_on_input,
+            screensaver,
+            stage: Stage::Active,
+            scheduled_off: false,
+            capped: false,
+            seen: None,
+        }
+    }
+
+    pub fn step(&mut self, t: Tick) -> Step {
+        let mut actions = Vec::new();
+        let mut last_activity = t.last_activity;
+        let mut screen_off = t.screen_off;
+        // Anything else moving `last_activity` was a tap
+        let mut touched = match self.seen {
+            Some((returned, touched)) if returned == t.last_activity => touched,
+            _ => t.last_activity,
+        };
+        match t.keep_awake {
+            KeepAwake::Off => {}
+            k if k.active(t.now) => last_activity = t.now,
+            _ => actions.push(Action::KeepAwakeExpired),
+        }
+        // While blank, the wake overlay decides when to wake, unless any
+        // input does (screensavers still take their tap). In the background
+        // the home screen does the waking.
+        let watch_input = t.background || !screen_off || (self.wake_on_input && !t.screensaver_up);
+        if let Some(last_input) = t.last_input.filter(|_| watch_input) {
+            if last_input > touched {
+                touched = last_input;
+                last_activity = last_activity.max(last_input);
+                if screen_off && !t.background {
+                    actions.push(Action::Wake { scheduled: false });
+                    screen_off = false;
+                }
+            }
+        }
+        let target = self.stages.stage(t.now.saturating_duration_since(last_activity));
+        self.seen = Some((last_activity, touched));
+        let step = |screen_off, actions| Step { last_activity, screen_off, actions };
+
+        if t.background {
+            if target == Stage::Disconnected && self.stage != target {
+                actions.push(Action::Disconnect);
+                self.stage = target;
+            }
+            return step(screen_off, actions);
+        }
+
+        // Screen-off hours win over activity; a touch lights the screen for
+        // `wake_for` (Keep awake does not). Disconnecting after a long idle
+        // still happens.
+        if t.scheduled.screen_off && target != Stage::Disconnected {
+            let woken = self.wake_for.is_some_and(|w| t.now.saturating_duration_since(touched) < w);
+            if (!screen_off || t.screensaver_up) && !woken {
+                actions.push(Action::ScreenOff { scheduled: true });
+                screen_off = true;
+            }
+            self.stage = Stage::Off;
+            self.scheduled_off = true;
+            return step(screen_off, actions);
+        }
+        // Screen-off hours are over: nothing else lights the screen then
+        if std::mem::take(&mut self.scheduled_off) && screen_off && target < Stage::Off {
+            actions.push(Action::Wake { scheduled: true });
+            screen_off = false;
+        }
+        if target != self.stage {
+            match target {
+                Stage::Active if self.stage == Stage::Dimmed => actions.push(Action::Restore),
+                // Woken from Off, by the wake overlay or above
+                Stage::Active => {}
+                Stage::Dimmed => actions.push(Action::Dim(self.stages.dim_percent)),
+                Stage::Off if screen_off => {}
+                Stage::Off => {
+                    actions.push(if self.screensaver { Action::Screensaver } else { Action::ScreenOff { scheduled: false } });
+                    screen_off = true;
+                }
+                Stage::Disconnected => actions.push(Action::Disconnect),
+            }
+            self.stage = target;
+        }
+        // Ambient light, then the scheduled cap while in use; dimming only
+        // goes lower
+        if self.stage == Stage::Active && !screen_off {
+            actions.push(Action::AutoBrightness);
+            match t.scheduled.brightness {
+                Some(percent) => {
+                    actions.push(Action::Cap { percent, starts: !self.capped });
+                    self.capped = true;
+                }
+                None if self.capped => {
+                    actions.push(Action::Uncap);
+                    self.capped = false;
+                }
+                None => {}
+            }
+        }
+        step(screen_off, actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_follow_idle_time() {
+        let stages = IdleStages::from_settings(&IdleSettings {
+            dim_after_secs: 60,
+            dim_percent: 20,
+            off_after_secs: 120,
+            disconnect_after_mins: 10,
+            disconnect_when_off_mins: 0,
+            home_off_after_secs: 30,
+            keep_awake_mins: 0,
+            wake: Default::default(),
+        });
+        let at = |secs| stages.stage(Duration::from_secs(secs));
+        assert_eq!(at(0), Stage::Active);
+        assert_eq!(at(59), Stage::Active);
+        assert_eq!(at(60), Stage::Dimmed);
+        assert_eq!(at(120), Stage::Off);
+        assert_eq!(at(600), Stage::Disconnected);
+
+        // Disabled stages are skipped
+        let no_dim = IdleStages { dim_after: None, ..stages.clone() };
+        assert_eq!(no_dim.stage(Duration::from_secs(90)), Stage::Active);
+        let never = IdleStages::from_settings(&IdleSettings {
+            dim_after_secs: 0,
+            off_after_secs: 0,
+            disconnect_after_mins: 0,
+            ..IdleSettings::default()
+        });
+        assert_eq!(never.stage(Duration::from_secs(86_400)), Stage::Active);
+
+        // The home screen blanks on its own timeout and never disconnects
+        let home = IdleStages::home_screen(&IdleSettings { dim_after_secs: 20, ..IdleSettings::default() });
+        assert_eq!(home.stage(Duration::from_secs(20)), Stage::Dimmed);
+        assert_eq!(home.stage(Duration::from_secs(60)), Stage::Off);
+        assert_eq!(home.stage(Duration::from_secs(86_400)), Stage::Off);
+    }
+
+    #[test]
+    fn keep_awake_expires() {
+        let now = Instant::now();
+        let hour = Duration::from_secs(3600);
+        let timed = KeepAwake::start(now, Some(hour));
+        assert!(timed.active(now));
+        assert_eq!(timed.remaining(now + Duration::from_secs(600)), Some(Duration::from_secs(3000)));
+        assert!(!timed.active(now + hour), "must expire after an hour");
+        assert_eq!(timed.remaining(now + hour * 2), Some(Duration::ZERO));
+
+        let forever = KeepAwake::start(now, None);
+        assert!(forever.active(now + hour * 24));
+        assert_eq!(forever.remaining(now), None);
+        assert!(!KeepAwake::default().active(now));
+    }
+
+    fn timer(wake_for: Option<u64>, wake_on_input: bool, screensaver: bool) -> IdleTimer {
+        let stages = IdleStages {
+            dim_after: Some(Duration::from_secs(60)),
+            dim_percent: 20,
+            off_after: Some(Duration::from_secs(120)),
+            disconnect_after: Some(Duration::from_secs(600)),
+        };
+        IdleTimer::new(stages, wake_for.map(Duration::from_secs), wake_on_input, screensaver)
+    }
+
+    /// A tick `idle` seconds after the last activity.
+    fn tick(start: Instant, idle: u64) -> Tick {
+        Tick {
+            now: start + Duration::from_secs(idle),
+            last_activity: start,
+            last_input: None,
+            keep_awake: KeepAwake::Off,
+            screen_off: false,
+            screensaver_up: false,
+            scheduled: Scheduled::default(),
+            background: false,
+        }
+    }
+
+    #[test]
+    fn dims_restores_and_goes_off() {
+        let start = Instant::now();
+        let mut t = timer(None, false, false);
+        assert_eq!(t.step(tick(start, 0)).actions, [Action::AutoBrightness]);
+        assert_eq!(t.step(tick(start, 60)).actions, [Action::Dim(20)]);
+        // Dimmed, no more ambient readings
+        assert!(t.step(tick(start, 61)).actions.is_empty());
+
+        // Input while dimmed counts as activity and brings brightness back
+        let input = Tick { last_input: Some(start + Duration::from_secs(61)), ..tick(start, 62) };
+        let step = t.step(input);
+        assert_eq!(step.last_activity, start + Duration::from_secs(61));
+        assert_eq!(step.actions, [Action::Restore, Action::AutoBrightness]);
+
+        let mut t = timer(None, false, false);
+        let step = t.step(tick(start, 120));
+        assert_eq!(step.actions, [Action::ScreenOff { scheduled: false }]);
+        assert!(step.screen_off);
+        // Already off: nothing more until it disconnects
+        assert!(t.step(Tick { screen_off: true, ..tick(start, 121) }).actions.is_empty());
+        assert_eq!(t.step(Tick { screen_off: true, ..tick(start, 600) }).actions, [Action::Disconnect]);
+    }
+
+    #[test]
+    fn screensaver_replaces_blanking() {
+        let start = Instant::now();
+        let mut t = timer(None, false, true);
+        let step = t.step(tick(start, 120));
+        assert_eq!(step.actions, [Action::Screensaver]);
+        assert!(step.screen_off);
+    }
+
+    #[test]
+    fn keep_awake_holds_the_countdown_until_it_expires() {
+        let start = Instant::now();
+        let mut t = timer(None, false, false);
+        let held = Tick { keep_awake: KeepAwake::Until(start + Duration::from_secs(300)), ..tick(start, 200) };
+        let step = t.step(held);
+        assert_eq!(step.last_activity, held.now);
+        assert_eq!(step.actions, [Action::AutoBrightness]);
+
+        let expired = Tick { now: start + Duration::from_secs(300), last_activity: held.now, ..held };
+        assert_eq!(t.step(expired).actions, [Action::KeepAwakeExpired, Action::Dim(20)]);
+    }
+
+    #[test]
+    fn input_wakes_only_when_asked_to() {
+        let start = Instant::now();
+        let blank = |t: &mut IdleTimer| {
+            t.step(tick(start, 120));
+            Tick { screen_off: true, last_input: Some(start + Duration::from_secs(130)), ..tick(start, 131) }
+        };
+
+        // The wake overlay takes the touch
+        let mut t = timer(None, false, false);
+        let touched = blank(&mut t);
+        let step = t.step(touched);
+        assert!(step.screen_off);
+        assert_eq!(step.last_activity, start);
+        assert!(step.actions.is_empty());
+
+        let mut t = timer(None, true, false);
+        let touched = blank(&mut t);
+        let step = t.step(touched);
+        assert!(!step.screen_off);
+        assert_eq!(step.last_activity, start + Duration::from_secs(130));
+        assert_eq!(step.actions, [Action::Wake { scheduled: false }, Action::AutoBrightness]);
+
+        // Screensavers still take their tap
+        let mut t = timer(None, true, true);
+        let touched = blank(&mut t);
+        let step = t.step(Tick { screensaver_up: true, ..touched });
+        assert!(step.screen_off);
+        assert!(step.actions.is_empty());
+    }
+
+    #[test]
+    fn schedule_turns_off_caps_and_still_disconnects() {
+        let start = Instant::now();
+        let off = Scheduled { screen_off: true, brightness: None };
+
+        // Screen-off hours win over activity, and replace a screensaver
+        let mut t = timer(None, false, true);
+        let step = t.step(Tick { scheduled: off, ..tick(start, 0) });
+        assert_eq!(step.actions, [Action::ScreenOff { scheduled: true }]);
+        assert!(step.screen_off);
+        assert!(t.step(Tick { scheduled: off, screen_off: true, ..tick(start, 1) }).actions.is_empty());
+        let saver = Tick { scheduled: off, screen_off: true, screensaver_up: true, ..tick(start, 2) };
+        assert_eq!(t.step(saver).actions, [Action::ScreenOff { scheduled: true }]);
+        let late = Tick { scheduled: off, screen_off: true, ..tick(start, 600) };
+        assert_eq!(t.step(late).actions, [Action::Disconnect]);
+
+        // A touch lights the screen for `wake_for`
+        let mut t = timer(Some(30), false, false);
+        assert!(t.step(Tick { scheduled: off, ..tick(start, 10) }).actions.is_empty());
+        assert_eq!(t.step(Tick { scheduled: off, ..tick(start, 30) }).actions, [Action::ScreenOff { scheduled: true }]);
+
+        // The cap starts once, holds while in use and ends
+        let mut t = timer(None, false, false);
+        let capped = Tick { scheduled: Scheduled { screen_off: false, brightness: Some(40) }, ..tick(start, 0) };
+        let cap = |starts| [Action::AutoBrightness, Action::Cap { percent: 40, starts }];
+        assert_eq!(t.step(capped).actions, cap(true));
+        assert_eq!(t.step(capped).actions, cap(false));
+        assert_eq!(t.step(tick(start, 1)).actions, [Action::AutoBrightness, Action::Uncap]);
+        assert_eq!(t.step(tick(start, 2)).actions, [Action::AutoBrightness]);
+    }
+
+    #[test]
+    fn keep_awake_does_not_count_as_a_touch_in_screen_off_hours() {
+        let start = Instant::now();
+        let off = Scheduled { screen_off: true, brightness: None };
+        let mut t = timer(Some(30), true, false);
+        let held = |secs, last_activity, screen_off| Tick {
+            last_activity,
+            keep_awake: KeepAwake::On,
+            screen_off,
+            scheduled: off,
+            ..tick(start, secs)
+        };
+
+        // Touched at the start: lit for 30s, then off despite Keep awake
+        let step = t.step(held(10, start, false));
+        assert!(step.actions.is_empty());
+        let step = t.step(held(20, step.last_activity, false));
+        assert!(step.actions.is_empty());
+        let step = t.step(held(30, step.last_activity, false));
+        assert_eq!(step.actions, [Action::ScreenOff { scheduled: true }]);
+        assert_eq!(step.last_activity, start + Duration::from_secs(30));
+
+        // A real touch still wakes it for a while
+        let touch = Tick { last_input: Some(start + Duration::from_secs(40)), ..held(41, step.last_activity, true) };
+        let step = t.step(touch);
+        assert_eq!(step.actions, [Action::Wake { scheduled: false }]);
+        assert!(!step.screen_off);
+        let step = t.step(held(69, step.last_activity, false));
+        assert!(step.actions.is_empty());
+        let step = t.step(held(70, step.last_activity, false));
+        assert_eq!(step.actions, [Action::ScreenOff { scheduled: true }]);
+    }
+
+    #[test]
+    fn in_the_background_only_disconnecting_counts() {
+        let start = Instant::now();
+        let off = Scheduled { screen_off: true, brightness: Some(40) };
+        let mut t = timer(Some(30), true, true);
+        let parked = |secs, screen_off| Tick { background: true, screen_off, scheduled: off, ..tick(start, secs) };
+        assert!(t.step(parked(0, false)).actions.is_empty());
+        assert!(t.step(parked(60, false)).actions.is_empty(), "no dimming");
+        let step = t.step(parked(120, true));
+        assert!(step.actions.is_empty(), "the home screen blanks, not us");
+        assert!(step.screen_off);
+        let input = Tick { last_input: Some(start + Duration::from_secs(130)), ..parked(131, true) };
+        let step = t.step(input);
+        assert!(step.actions.is_empty(), "nor wakes");
+        assert_eq!(step.last_activity, start + Duration::from_secs(130), "but input still counts");
+        let step = t.step(Tick { last_activity: step.last_activity, ..parked(730, true) });
+        assert_eq!(step.actions, [Action::Disconnect]);
+    }
+
+    #[test]
+    fn the_end_of_screen_off_hours_wakes_the_screen() {
+        let start = Instant::now();
+        let night = Scheduled { screen_off: true, brightness: None };
+        let evening = Scheduled { screen_off: false, brightness: Some(40) };
+        let mut t = timer(None, false, false);
+        let held = |secs, screen_off, scheduled| Tick {
+            keep_awake: KeepAwake::On,
+            screen_off,
+            scheduled,
+            ..tick(start, secs)
+        };
+        assert_eq!(t.step(held(0, false, night)).actions, [Action::ScreenOff { scheduled: true }]);
+        assert!(t.step(held(1, true, night)).actions.is_empty());
+        let step = t.step(held(2, true, evening));
+        assert_eq!(
+            step.actions,
+            [Action::Wake { scheduled: true }, Action::AutoBrightness, Action::Cap { percent: 40, starts: true }]
+        );
+        assert!(!step.screen_off);
+
+        // Off from inactivity before the hours ended: it stays off
+        let mut t = timer(None, false, false);
+        t.step(Tick { scheduled: night, ..tick(start, 0) });
+        let step = t.step(Tick { screen_off: true, ..tick(start, 300) });
+        assert!(step.actions.is_empty());
+        assert!(step.screen_off);
+    }
+}
@@ -0,0 +1,139 @@
+//! Screen power through `zwlr_output_power_management_v1`.
+//!
+//! Works under any wlroots compositor that offers the protocol (labwc,
+//! Wayfire, sway), so screen blanking does not depend on sway IPC. Each call
+//! opens its own short-lived Wayland connection: binds every `wl_output`,
+//! asks for the mode and waits one roundtrip for the compositor to confirm or
+//! report a failure. The mode stays set after the connection closes.
+
+use wayland_client::globals::{registry_queue_init, Global, GlobalListContents};
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle};
+use wayland_protocols_wlr::output_power_management::v1::client::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1;
+use wayland_protocols_wlr::output_power_management::v1::client::zwlr_output_power_v1::{
+    self, Mode, ZwlrOutputPowerV1,
+};
+
+/// What the compositor reported for the outputs' power controls.
+#[derive(Debug, Default, PartialEq)]
+struct State {
+    /// Mode events: the mode on binding, then each change.
+    reported: usize,
+    failed: usize,
+}
+
+impl State {
+    fn record(&mut self, event: zwlr_output_power_v1::Event) {
+        match event {
+            zwlr_output_power_v1::Event::Mode { .. } => self.reported += 1,
+            zwlr_output_power_v1::Event::Failed => self.failed += 1,
+            _ => {}
+        }
+    }
+
+    /// Result for `outputs` outputs once the compositor has answered.
+    fn outcome(&self, outputs: usize) -> Result<usize, String> {
+        if self.failed > 0 {
+            return Err(format!("{} of {} outputs refused the power mode", self.failed, outputs));
+        }
+        Ok(outputs)
+    }
+}
+
+/// `(name, version)` of every `wl_output` global, at the version we speak.
+fn outputs(globals: &[Global]) -> Vec<(u32, u32)> {
+    globals.iter().filter(|g| g.interface == "wl_output").map(|g| (g.name, g.version.min(1))).collect()
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        state.record(event);
+    }
+}
+
+delegate_noop!(State: ignore WlOutput);
+delegate_noop!(State: ZwlrOutputPowerManagerV1);
+
+/// Switch all outputs on or off. Returns how many outputs were switched.
+pub fn set_power(on: bool) -> Result<usize, String> {
+    let conn = Connection::connect_to_env().map_err(|e| format!("No Wayland display: {}", e))?;
+    let (globals, mut queue) =
+        registry_queue_init::<State>(&conn).map_err(|e| format!("Wayland registry failed: {}", e))?;
+    let qh = queue.handle();
+    let manager: ZwlrOutputPowerManagerV1 = globals
+        .bind(&qh, 1..=1, ())
+        .map_err(|_| "Compositor does not support wlr-output-power-management".to_string())?;
+
+    let output_names = globals.contents().with_list(outputs);
+    if output_names.is_empty() {
+        return Err("Compositor reports no outputs".to_string());
+    }
+    let mode = if on { Mode::On } else { Mode::Off };
+    let mut controls = Vec::new();
+    for (name, version) in output_names {
+        let output: WlOutput = globals.registry().bind(name, version, &qh, ());
+        let power = manager.get_output_power(&output, &qh, ());
+        power.set_mode(mode);
+        controls.push((power, output));
+    }
+
+    let mut state = State::default();
+    // The compositor answers each set_mode with a mode or `failed` event
+    queue.roundtrip(&mut state).map_err(|e| format!("Wayland roundtrip failed: {}", e))?;
+
+    for (power, _) in &controls {
+        power.destroy();
+    }
+    manager.destroy();
+    let _ = conn.flush();
+
+    state.outcome(controls.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wayland_client::WEnum;
+
+    #[test]
+    fn binds_outputs_and_counts_refusals() {
+        let global = |name: u32, interface: &str, version: u32| Global { name, interface: interface.to_string(), version };
+        let globals = [
+            global(1, "wl_compositor", 6),
+            global(4, "wl_output", 4),
+            global(7, "zwlr_output_power_manager_v1", 1),
+            global(9, "wl_output", 1),
+        ];
+        assert_eq!(outputs(&globals), [(4, 1), (9, 1)]);
+        assert!(outputs(&globals[..1]).is_empty());
+
+        let mut state = State::default();
+        state.record(zwlr_output_power_v1::Event::Mode { mode: WEnum::Value(Mode::On) });
+        state.record(zwlr_output_power_v1::Event::Mode { mode: WEnum::Value(Mode::Off) });
+        assert_eq!(state, State { reported: 2, failed: 0 });
+        assert_eq!(state.outcome(2), Ok(2));
+
+        state.record(zwlr_output_power_v1::Event::Failed);
+        assert_eq!(state.outcome(2), Err("1 of 2 outputs refused the power mode".to_string()));
+    }
+}
@@ -0,0 +1,137 @@
+//! Parked Mote view sessions.
+//!
+//! Home parks the RDP window on a hidden sway workspace instead of ending the
+//! session. The Mote view process and the home screen coordinate through
+//! files next to the mote-active flag: the Mote view writes its pid and the
+//! profile name to `mote-parked` while parked, and the home screen (or a
+//! second launch of the same profile) leaves a `mote-request` asking it to
+//! resume or disconnect. While a session is parked the home screen also
+//! keeps `mote-screen-off` while it is blank, so the parked session's idle
+//! timer knows.
+
+use std::path::Path;
+
+use crate::log;
+
+pub const PARK_DIR: &str = "/tmp";
+const PARKED_FILE: &str = "mote-parked";
+const REQUEST_FILE: &str = "mote-request";
+const SCREEN_OFF_FILE: &str = "mote-screen-off";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Resume,
+    Disconnect,
+}
+
+/// Record that the session for `profile` is parked by this process.
+pub fn park(dir: &Path, profile: &str) {
+    let _ = std::fs::remove_file(dir.join(REQUEST_FILE));
+    std::fs::write(dir.join(PARKED_FILE), format!("{}\n{}", std::process::id(), profile)).ok();
+}
+
+pub fn unpark(dir: &Path) {
+    std::fs::remove_file(dir.join(PARKED_FILE)).ok();
+    std::fs::remove_file(dir.join(SCREEN_OFF_FILE)).ok();
+}
+
+/// Profile name of the parked session, if there is one. A session whose
+/// Mote view is gone (crashed, killed) is not parked any more, and its files
+/// are removed.
+pub fn parked(dir: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(dir.join(PARKED_FILE)).ok()?;
+    let (pid, profile) = text.split_once('\n')?;
+    let alive = pid.trim().parse::<u32>().is_ok_and(|pid| Path::new(&format!("/proc/{}", pid)).exists());
+    if !alive {
+        log(&format!("Parked session for {} is gone - forgetting it", profile.trim()));
+        unpark(dir);
+        std::fs::remove_file(dir.join(REQUEST_FILE)).ok();
+        return None;
+    }
+    Some(profile.trim().to_string())
+}
+
+/// Tell the parked session whether the home screen is blank.
+pub fn set_screen_off(dir: &Path, off: bool) {
+    let path = dir.join(SCREEN_OFF_FILE);
+    if off && !path.exists() {
+        std::fs::write(path, "").ok();
+    } else if !off && path.exists() {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+pub fn screen_off(dir: &Path) -> bool {
+    dir.join(SCREEN_OFF_FILE).exists()
+}
+
+pub fn request(dir: &Path, request: Request) {
+    let text = match request {
+        Request::Resume => "resume",
+        Request::Disconnect => "disconnect",
+    };
+    std::fs::write(dir.join(REQUEST_FILE), text).ok();
+}
+
+/// The pending request, removing it.
+pub fn take_request(dir: &Path) -> Option<Request> {
+    let path = dir.join(REQUEST_FILE);
+    let text = std::fs::read_to_string(&path).ok()?;
+    std::fs::remove_file(&path).ok();
+    match text.trim() {
+        "resume" => Some(Request::Resume),
+        "disconnect" => Some(Request::Disconnect),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn park_and_request_round_trip() {
+        let dir = std::env::temp_dir().join(format!("mote-park-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(parked(&dir), None);
+        request(&dir, Request::Disconnect);
+        park(&dir, "Living room");
+        assert_eq!(take_request(&dir), None, "parking clears stale requests");
+        assert_eq!(parked(&dir).as_deref(), Some("Living room"));
+
+        request(&dir, Request::Resume);
+        assert_eq!(take_request(&dir), Some(Request::Resume));
+        assert_eq!(take_request(&dir), None, "a request is handled once");
+        set_screen_off(&dir, true);
+        assert!(screen_off(&dir));
+        set_screen_off(&dir, false);
+        assert!(!screen_off(&dir));
+        set_screen_off(&dir, true);
+        unpark(&dir);
+        assert_eq!(parked(&dir), None);
+        assert!(!screen_off(&dir), "unparking clears the screen state");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_dead_owner_is_not_parked() {
+        let dir = std::env::temp_dir().join(format!("mote-park-dead-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        std::fs::write(dir.join(PARKED_FILE), format!("{}\nLiving room", child.id())).unwrap();
+        request(&dir, Request::Resume);
+        set_screen_off(&dir, true);
+        assert_eq!(parked(&dir), None);
+        assert!(!dir.join(PARKED_FILE).exists(), "the stale file must go");
+        assert_eq!(take_request(&dir), None);
+        assert!(!screen_off(&dir));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
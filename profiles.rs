@@ -0,0 +1,224 @@
+//! Named RDP connection profiles read from `~/.config/mote/profiles.toml`.
+//!
+//! ```toml
+//! [[profile]]
+//! name = "Living room"
+//! host = "10.1.1.3"
+//! user = "tv"
+//! credential = "living-room"
+//! freerdp_options = ["/sound"]
+//! ```
+//!
+//! Each profile gets its own Mote icon on the home screen. Without the file a
+//! single "Mote" profile is built from `ANDROID_HOST` / `MOTE_RDP_USER`.
+
+use serde::Deserialize;
+use std::env;
+use std::path::Path;
+
+use crate::config::{self, IconConfig};
+use crate::credentials;
+use crate::log;
+
+fn default_port() -> u16 {
+    3389
+}
+
+// 7" touchscreen native resolution
+fn default_width() -> u32 {
+    800
+}
+
+fn default_height() -> u32 {
+    480
+}
+
+fn default_icon() -> String {
+    "video-display".to_string()
+}
+
+fn default_reconnect_attempts() -> u32 {
+    5
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Profile {
+    /// Label on the home-screen icon and argument to `--mote-view`.
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub user: String,
+    /// ID of the password in the credential store (the profile name when
+    /// unset).
+    #[serde(default)]
+    pub credential: Option<String>,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    /// Extra sdl-freerdp3 options, appended as-is (e.g. "/sound", "-wallpaper").
+    #[serde(default)]
+    pub freerdp_options: Vec<String>,
+    #[serde(default = "default_icon")]
+    pub icon: String,
+    /// How often to retry after an established session drops (0 disables).
+    #[serde(default = "default_reconnect_attempts")]
+    pub reconnect_attempts: u32,
+    /// Home-screen position, same scale as `order` in icons.toml.
+    #[serde(default)]
+    pub order: i32,
+    /// Start with "Keep awake" on (dashboards, video).
+    #[serde(default)]
+    pub keep_awake: bool,
+}
+
+#[derive(Deserialize)]
+struct ProfilesFile {
+    #[serde(default, rename = "profile")]
+    profiles: Vec<Profile>,
+}
+
+impl Profile {
+    pub fn new(name: &str, host: &str, user: &str) -> Self {
+        Profile {
+            name: name.to_string(),
+            host: host.to_string(),
+            port: default_port(),
+            user: user.to_string(),
+            credential: None,
+            width: default_width(),
+            height: default_height(),
+            freerdp_options: Vec::new(),
+            icon: default_icon(),
+            reconnect_attempts: default_reconnect_attempts(),
+            order: 0,
+            keep_awake: false,
+        }
+    }
+
+    /// The single profile used before profiles existed, from the old env vars.
+    pub fn from_env() -> Self {
+        let host = env::var("ANDROID_HOST").unwrap_or_else(|_| "10.1.1.3".to_string());
+        let user = env::var("MOTE_RDP_USER").unwrap_or_else(|_| "tv".to_string());
+        Profile::new("Mote", &host, &user)
+    }
+
+    /// sdl-freerdp3 arguments for this profile, without credentials or
+    /// certificate options.
+    ///
+    /// KRDP uses NLA authentication with username/password. Resolution is
+    /// set via /w: /h: so the server renders at the panel's native size (no
+    /// client-side scaling needed). +multitouch enables touch redirection
+    /// over the RDP protocol.
+    pub fn freerdp_args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("/u:{}", self.user),
+            format!("/w:{}", self.width),
+            format!("/h:{}", self.height),
+            "/f".to_string(),
+            "+multitouch".to_string(),
+        ];
+        args.extend(self.freerdp_options.iter().cloned());
+        if self.port != default_port() {
+            args.push(format!("/port:{}", self.port));
+        }
+        args.push(format!("/v:{}", self.host));
+        args
+    }
+
+    pub fn credential_id(&self) -> &str {
+        self.credential.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Password for this profile from the credential store. One still in
+    /// `MOTE_RDP_PASSWORD` from before the store is moved into it.
+    pub fn password(&self) -> Result<String, credentials::Error> {
+        credentials::lookup_rdp(self.credential_id())
+    }
+
+    /// Home-screen icon that opens this profile in Mote view.
+    pub fn icon_config(&self) -> IconConfig {
+        IconConfig {
+            label: self.name.clone(),
+            icon: self.icon.clone(),
+            command: format!("{} --mote-view {}", config::EXE_PLACEHOLDER, shell_quote(&self.name)),
+            order: self.order,
+        }
+    }
+}
+
+/// Quote `s` for `sh -c` so profile names may contain spaces or quotes.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+pub fn parse_profiles(text: &str) -> Result<Vec<Profile>, String> {
+    let file: ProfilesFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    Ok(file.profiles)
+}
+
+/// Load profiles from `profiles.toml`, falling back to the env-var profile
+/// when the file is missing, invalid or empty.
+pub fn load_profiles() -> Vec<Profile> {
+    load_profiles_from(&config::config_dir().join("profiles.toml"))
+}
+
+pub fn load_profiles_from(path: &Path) -> Vec<Profile> {
+    let profiles = match std::fs::read_to_string(path) {
+        Ok(text) => match parse_profiles(&text) {
+            Ok(p) => p,
+            Err(e) => {
+                log(&format!("Invalid profiles {}: {} - using env profile", path.display(), e));
+                Vec::new()
+            }
+        },
+        Err(_) => Vec::new(),
+    };
+    if profiles.is_empty() {
+        return vec![Profile::from_env()];
+    }
+    log(&format!("Loaded {} profiles from {}", profiles.len(), path.display()));
+    profiles
+}
+
+/// Look up a profile by name; `None` picks the first one.
+pub fn find_profile(profiles: &[Profile], name: Option<&str>) -> Option<Profile> {
+    match name {
+        Some(n) => profiles.iter().find(|p| p.name == n).cloned(),
+        None => profiles.first().cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_parse_with_defaults() {
+        let profiles = parse_profiles(
+            r#"
+            [[profile]]
+            name = "Office"
+            host = "office.lan"
+            user = "me"
+            port = 3390
+            freerdp_options = ["/sound"]
+            "#,
+        )
+        .expect("valid profiles.toml");
+        let p = &profiles[0];
+        assert_eq!((p.width, p.height), (800, 480));
+        assert!(!p.keep_awake);
+        let args = p.freerdp_args();
+        assert!(args.contains(&"/port:3390".to_string()), "missing port: {:?}", args);
+        assert!(args.contains(&"/sound".to_string()), "missing option: {:?}", args);
+        assert_eq!(args.last().map(String::as_str), Some("/v:office.lan"));
+    }
+
+    #[test]
+    fn profile_icon_quotes_name() {
+        let icon = Profile::new("Kid's room", "10.1.1.4", "tv").icon_config();
+        assert_eq!(icon.command, r#"{exe} --mote-view 'Kid'\''s room'"#);
+    }
+}
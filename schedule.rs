@@ -0,0 +1,119 @@
+//! Time-of-day display schedule ("night mode"): windows during which the
+//! screen stays off or its brightness is capped, whatever the activity.
+//! Configured in the `[schedule]` section of settings.toml; evaluated by the
+//! idle timer against local time.
+
+use std::time::Duration;
+
+use chrono::NaiveTime;
+
+use crate::settings::{ScheduleSettings, ScheduleWindow};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    from: NaiveTime,
+    to: NaiveTime,
+    screen_off: bool,
+    brightness: Option<u32>,
+}
+
+impl Window {
+    /// `from` inclusive, `to` exclusive; windows may wrap past midnight.
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            time >= self.from || time < self.to
+        }
+    }
+}
+
+/// What the schedule asks for at a given time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Scheduled {
+    pub screen_off: bool,
+    /// Brightness cap in percent of `max_brightness`.
+    pub brightness: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schedule {
+    windows: Vec<Window>,
+    /// How long a touch lights the screen during screen-off hours (`None`:
+    /// it goes straight back off).
+    pub wake_for: Option<Duration>,
+}
+
+impl Schedule {
+    pub fn from_settings(s: &ScheduleSettings) -> Result<Self, String> {
+        let windows = s.windows.iter().map(parse_window).collect::<Result<_, _>>()?;
+        Ok(Schedule {
+            windows,
+            wake_for: (s.wake_on_touch_secs > 0).then(|| Duration::from_secs(s.wake_on_touch_secs)),
+        })
+    }
+
+    /// Overlapping windows combine: any screen-off wins, the lowest cap wins.
+    pub fn at(&self, time: NaiveTime) -> Scheduled {
+        self.windows.iter().filter(|w| w.contains(time)).fold(Scheduled::default(), |acc, w| Scheduled {
+            screen_off: acc.screen_off || w.screen_off,
+            brightness: match (acc.brightness, w.brightness) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        })
+    }
+}
+
+fn parse_time(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").map_err(|_| format!("invalid time \"{}\" (expected HH:MM)", text))
+}
+
+fn parse_window(w: &ScheduleWindow) -> Result<Window, String> {
+    let (from, to) = (parse_time(&w.from)?, parse_time(&w.to)?);
+    if from == to {
+        return Err(format!("schedule window {}-{} is empty", w.from, w.to));
+    }
+    Ok(Window { from, to, screen_off: w.screen_off, brightness: w.brightness.map(|b| b.min(100)) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::parse_settings;
+
+    #[test]
+    fn windows_wrap_midnight_and_combine() {
+        let settings = parse_settings(
+            r#"
+            [schedule]
+            wake_on_touch_secs = 45
+            [[schedule.window]]
+            from = "23:00"
+            to = "07:00"
+            screen_off = true
+            [[schedule.window]]
+            from = "19:00"
+            to = "23:30"
+            brightness = 30
+            "#,
+        )
+        .expect("parse");
+        let schedule = Schedule::from_settings(&settings.schedule).expect("valid schedule");
+        assert_eq!(schedule.wake_for, Some(Duration::from_secs(45)));
+        let at = |t: &str| schedule.at(parse_time(t).unwrap());
+        assert_eq!(at("12:00"), Scheduled::default());
+        assert_eq!(at("19:00"), Scheduled { screen_off: false, brightness: Some(30) });
+        assert_eq!(at("23:15"), Scheduled { screen_off: true, brightness: Some(30) });
+        assert_eq!(at("03:00"), Scheduled { screen_off: true, brightness: None });
+        assert_eq!(at("07:00"), Scheduled::default(), "to is exclusive");
+
+        let bad = |from: &str, to: &str| ScheduleSettings {
+            windows: vec![ScheduleWindow { from: from.into(), to: to.into(), screen_off: true, brightness: None }],
+            ..ScheduleSettings::default()
+        };
+        assert!(Schedule::from_settings(&bad("25:00", "07:00")).is_err());
+        assert!(Schedule::from_settings(&bad("7", "08:00")).is_err());
+        assert!(Schedule::from_settings(&bad("07:00", "07:00")).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! Screensaver content shown instead of blanking when idle (see
+//! `[screensaver]` in settings.toml). The windows themselves live with the
+//! other overlays in desktop-icons.rs.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Image types the photo frame shows (lower case).
+const PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+
+/// Images directly in `dir`, in name order.
+pub fn list_photos(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut photos: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| PHOTO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    photos.sort();
+    photos
+}
+
+/// Offset of the clock from the center at `step`, at most `range` pixels
+/// either way. Slow wandering path, so no pixel stays lit for long.
+pub fn drift_offset(step: u64, range: i32) -> (i32, i32) {
+    let t = step as f64;
+    let r = range as f64;
+    ((r * (t * 0.7).sin()).round() as i32, (r * (t * 0.45).cos()).round() as i32)
+}
+
+/// Whether something accepts TCP connections on `host:port`.
+pub fn host_reachable(host: &str, port: u16, timeout: Duration) -> bool {
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs.into_iter().any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_images_only() {
+        let dir = std::env::temp_dir().join(format!("mote-photos-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("album.jpg")).unwrap();
+        for name in ["b.JPG", "a.png", "notes.txt", "c.webp", "no-extension"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        assert_eq!(list_photos(&dir), [dir.join("a.png"), dir.join("b.JPG"), dir.join("c.webp")]);
+        assert!(list_photos(&dir.join("missing")).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clock_drifts_within_range_and_hosts_are_probed() {
+        let offsets: Vec<(i32, i32)> = (0..60).map(|step| drift_offset(step, 24)).collect();
+        assert!(offsets.iter().all(|&(x, y)| x.abs() <= 24 && y.abs() <= 24), "{:?}", offsets);
+        assert!(offsets.windows(2).all(|w| w[0] != w[1]), "must move every step: {:?}", offsets);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(host_reachable("127.0.0.1", port, Duration::from_secs(1)));
+        drop(listener);
+        // Port 0 never accepts connections
+        assert!(!host_reachable("127.0.0.1", 0, Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,509 @@
+//! Supervision of the sdl-freerdp3 process for the whole life of Mote view.
+//!
+//! A background thread owns the client process. When an established session
+//! drops it respawns the client with exponential backoff, up to the profile's
+//! `reconnect_attempts`, and reports progress as `Event`s that the GTK main
+//! loop polls with `Supervisor::try_event`. A session can also be suspended:
+//! the client is closed but the thread waits to start a new one on `resume`.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::failures::{self, Failure, FailureKind};
+use crate::freerdp::{self, Client, OutputLog};
+use crate::log;
+
+/// A client still running after this long counts as connected (the default
+/// for `ReconnectPolicy::stable_after`). Exits before that on the first try
+/// are connection failures, not drops.
+pub const STABLE_AFTER: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// FreeRDP lines quoted in failure messages.
+const REPORTED_LINES: usize = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub stable_after: Duration,
+}
+
+impl ReconnectPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        ReconnectPolicy {
+            max_attempts,
+            initial_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            stable_after: STABLE_AFTER,
+        }
+    }
+
+    /// Wait before reconnect `attempt` (1-based): 2s, 4s, 8s, ... capped.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The client has been up for the policy's `stable_after`.
+    Connected,
+    /// The very first connection failed, the session dropped for a reason
+    /// retrying cannot fix (see `FailureKind::retryable`), or it dropped
+    /// with reconnecting turned off.
+    Failed(Failure),
+    /// The session dropped; reconnect `attempt` starts after `delay`.
+    /// `reason` is the last error FreeRDP printed, if any.
+    Reconnecting { attempt: u32, delay: Duration, reason: Option<String> },
+    /// Reconnecting failed `attempts` times in a row.
+    GaveUp { attempts: u32, last_error: Failure },
+    /// The session was closed on purpose (logoff, disconnect by user).
+    Ended,
+}
+
+pub struct Supervisor {
+    stop: Arc<AtomicBool>,
+    suspended: Arc<AtomicBool>,
+    pid: Arc<AtomicU32>,
+    events: Receiver<Event>,
+}
+
+impl Supervisor {
+    /// Supervise `client`; `launch` starts a replacement after a drop. Both
+    /// write their output to `output`, which failures are classified from.
+    pub fn start<F>(
+        client: Client,
+        launch: F,
+        output: OutputLog,
+        policy: ReconnectPolicy,
+    ) -> Supervisor
+    where
+        F: FnMut() -> io::Result<Client> + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let suspended = Arc::new(AtomicBool::new(false));
+        let (tx, events) = mpsc::channel();
+        let pid = Arc::new(AtomicU32::new(client.child.id()));
+        let flags = Flags { stop: stop.clone(), suspended: suspended.clone() };
+        let thread_pid = pid.clone();
+        std::thread::spawn(move || supervise(client, launch, output, policy, flags, thread_pid, tx));
+        Supervisor { stop, suspended, pid, events }
+    }
+
+    /// Kill the client and stop reconnecting.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    /// Close the client without ending the session; `resume` connects again.
+    pub fn suspend(&self) {
+        self.suspended.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.suspended.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::SeqCst)
+    }
+
+    /// Process id of the current client (changes on every reconnect).
+    pub fn client_pid(&self) -> u32 {
+        self.pid.load(Ordering::SeqCst)
+    }
+
+    pub fn try_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+/// What the GTK side asks of the supervisor thread.
+struct Flags {
+    stop: Arc<AtomicBool>,
+    suspended: Arc<AtomicBool>,
+}
+
+impl Flags {
+    /// Wait for `resume`; false when stopped instead.
+    fn wait_for_resume(&self) -> bool {
+        while self.suspended.load(Ordering::SeqCst) {
+            if self.stop.load(Ordering::SeqCst) {
+                return false;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        !self.stop.load(Ordering::SeqCst)
+    }
+}
+
+/// How a client run ended.
+enum Exit {
+    Exited(Option<std::process::ExitStatus>),
+    /// Starting it again after a suspend failed.
+    ResumeFailed(io::Error),
+}
+
+fn restart_failure(e: io::Error) -> Failure {
+    Failure { kind: FailureKind::Unknown, details: vec![format!("Could not restart RDP client: {}", e)] }
+}
+
+fn supervise<F>(
+    client: Client,
+    mut launch: F,
+    output: OutputLog,
+    policy: ReconnectPolicy,
+    flags: Flags,
+    pid: Arc<AtomicU32>,
+    tx: Sender<Event>,
+) where
+    F: FnMut() -> io::Result<Client>,
+{
+    let stop = &flags.stop;
+    let mut attempt = 0;
+    let mut connected_once = false;
+    let mut next = Ok(client);
+    loop {
+        let failure = match next {
+            Ok(mut client) => {
+                let mut started = Instant::now();
+                let mut announced = false;
+                let exit = loop {
+                    if stop.load(Ordering::SeqCst) {
+                        log("Session stopped - killing sdl-freerdp3");
+                        let _ = client.child.kill();
+                        let _ = client.child.wait();
+                        return;
+                    }
+                    if flags.suspended.load(Ordering::SeqCst) {
+                        log("Session suspended - closing sdl-freerdp3");
+                        let _ = client.child.kill();
+                        let _ = client.child.wait();
+                        client.finish_output();
+                        if !flags.wait_for_resume() {
+                            return;
+                        }
+                        log("Session resumed - starting sdl-freerdp3");
+                        // The session was wanted up, so failing from here on
+                        // is a drop to reconnect from
+                        connected_once = true;
+                        attempt = 0;
+                        client = match launch() {
+                            Ok(c) => c,
+                            Err(e) => break Exit::ResumeFailed(e),
+                        };
+                        pid.store(client.child.id(), Ordering::SeqCst);
+                        started = Instant::now();
+                        announced = false;
+                        continue;
+                    }
+                    match client.child.try_wait() {
+                        Ok(Some(status)) => break Exit::Exited(Some(status)),
+                        Ok(None) => {}
+                        Err(e) => {
+                            log(&format!("Waiting for sdl-freerdp3 failed: {}", e));
+                            break Exit::Exited(None);
+                        }
+                    }
+                    if !announced && started.elapsed() >= policy.stable_after {
+                        announced = true;
+                        connected_once = true;
+                        attempt = 0;
+                        let _ = tx.send(Event::Connected);
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                };
+                match exit {
+                    Exit::Exited(status) => {
+                        log(&format!("sdl-freerdp3 exited ({:?}) after {:?}", status, started.elapsed()));
+                        if stop.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        client.finish_output();
+                        let code = status.and_then(|s| s.code());
+                        if code.is_some_and(freerdp::is_intentional_exit) {
+                            let _ = tx.send(Event::Ended);
+                            return;
+                        }
+                        let failure = Failure {
+                            kind: failures::classify(code, &output.lines()),
+                            details: output.relevant(REPORTED_LINES),
+                        };
+                        log(&format!("sdl-freerdp3 failure: {:?}", failure.kind));
+                        failure
+                    }
+                    Exit::ResumeFailed(e) => {
+                        log(&format!("Restarting sdl-freerdp3 failed: {}", e));
+                        restart_failure(e)
+                    }
+                }
+            }
+            Err(e) => {
+                log(&format!("Restarting sdl-freerdp3 failed: {}", e));
+                restart_failure(e)
+            }
+        };
+        if !connected_once || !failure.kind.retryable() || policy.max_attempts == 0 {
+            let _ = tx.send(Event::Failed(failure));
+            return;
+        }
+        attempt += 1;
+        if attempt > policy.max_attempts {
+            let _ = tx.send(Event::GaveUp { attempts: policy.max_attempts, last_error: failure });
+            return;
+        }
+        let delay = policy.delay(attempt);
+        log(&format!("Session dropped - reconnect attempt {} in {:?}", attempt, delay));
+        let _ = tx.send(Event::Reconnecting { attempt, delay, reason: failure.details.last().cloned() });
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            if stop.load(Ordering::SeqCst) {
+                return;
+            }
+            // No reconnecting with the screen off; resuming starts over
+            if flags.suspended.load(Ordering::SeqCst) {
+                log("Session suspended while waiting to reconnect");
+                if !flags.wait_for_resume() {
+                    return;
+                }
+                log("Session resumed - starting sdl-freerdp3");
+                attempt = 0;
+                break;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        next = launch();
+        if let Ok(c) = &next {
+            pid.store(c.child.id(), Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Stand-in client running `script`, with FreeRDP-style log lines.
+    fn client(script: &str, output: &OutputLog) -> io::Result<Client> {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        freerdp::start(cmd, "", output)
+    }
+
+    /// Fast policy: up for 300ms counts as connected, retries after 10ms.
+    fn policy(max_attempts: u32) -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+            stable_after: Duration::from_millis(300),
+        }
+    }
+
+    /// Supervise `first`; relaunches run `again` in turn, repeating the last.
+    /// Returns the supervisor and the number of relaunches so far.
+    fn supervise_script(
+        first: &str,
+        again: &'static [&'static str],
+        policy: ReconnectPolicy,
+    ) -> (Supervisor, Arc<AtomicUsize>) {
+        let output = OutputLog::new(freerdp::OUTPUT_LOG_LINES);
+        let launches = Arc::new(AtomicUsize::new(0));
+        let counter = launches.clone();
+        let relaunch_output = output.clone();
+        let launch = move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            client(again[run.min(again.len() - 1)], &relaunch_output)
+        };
+        let first = client(first, &output).expect("spawn sh");
+        (Supervisor::start(first, launch, output, policy), launches)
+    }
+
+    fn next_event(s: &Supervisor) -> Event {
+        s.events.recv_timeout(Duration::from_secs(10)).expect("supervisor event")
+    }
+
+    fn eventually(what: &str, done: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out waiting until {}", what);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn running(pid: u32) -> bool {
+        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+    }
+
+    const DROPPED: &str = "sleep 0.5; echo '[ERROR][com.freerdp.core] - Connection reset by peer'; exit 1";
+    const REFUSED: &str = "echo '[ERROR][com.freerdp.core] - connect to 10.1.1.3:3389 failed: Connection refused'; exit 1";
+    const TIMED_OUT: &str = "echo '[ERROR][com.freerdp.core] - connect to 10.1.1.3:3389 timed out'; exit 1";
+
+    #[test]
+    fn reconnect_delay_backs_off_exponentially() {
+        let policy = ReconnectPolicy::new(8);
+        let delays: Vec<u64> = (1..=7).map(|a| policy.delay(a).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(policy.delay(40), Duration::from_secs(60), "must not overflow");
+    }
+
+    #[test]
+    fn dropped_session_reconnects_then_gives_up() {
+        let (s, launches) = supervise_script(DROPPED, &[REFUSED], policy(2));
+        assert_eq!(next_event(&s), Event::Connected);
+        match next_event(&s) {
+            Event::Reconnecting { attempt: 1, reason, .. } => {
+                assert_eq!(reason.as_deref(), Some("ERROR: Connection reset by peer"))
+            }
+            other => panic!("expected the first reconnect, got {:?}", other),
+        }
+        assert!(matches!(next_event(&s), Event::Reconnecting { attempt: 2, .. }));
+        match next_event(&s) {
+            Event::GaveUp { attempts: 2, last_error } => assert_eq!(last_error.kind, FailureKind::Refused),
+            other => panic!("expected to give up, got {:?}", other),
+        }
+        assert_eq!(launches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn each_run_is_classified_from_its_own_output() {
+        // Refused is checked before timeouts, so a log still holding the
+        // previous run would report the last attempt as refused
+        let (s, _) = supervise_script(DROPPED, &[REFUSED, TIMED_OUT], policy(2));
+        assert_eq!(next_event(&s), Event::Connected);
+        assert!(matches!(next_event(&s), Event::Reconnecting { attempt: 1, .. }));
+        match next_event(&s) {
+            Event::Reconnecting { attempt: 2, reason, .. } => {
+                assert_eq!(reason.as_deref(), Some("ERROR: connect to 10.1.1.3:3389 failed: Connection refused"))
+            }
+            other => panic!("expected the second reconnect, got {:?}", other),
+        }
+        match next_event(&s) {
+            Event::GaveUp { last_error, .. } => {
+                assert_eq!(last_error.kind, FailureKind::Unreachable);
+                assert_eq!(last_error.details, ["ERROR: connect to 10.1.1.3:3389 timed out"]);
+            }
+            other => panic!("expected to give up, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn drop_without_reconnect_attempts_is_a_plain_failure() {
+        let (s, launches) = supervise_script(DROPPED, &[REFUSED], policy(0));
+        assert_eq!(next_event(&s), Event::Connected);
+        match next_event(&s) {
+            Event::Failed(failure) => assert_eq!(failure.kind, FailureKind::ServerDisconnect),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+        assert_eq!(launches.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn first_connect_failure_and_intentional_exit_are_not_retried() {
+        let (s, launches) = supervise_script(REFUSED, &[REFUSED], policy(5));
+        match next_event(&s) {
+            Event::Failed(failure) => assert_eq!(failure.kind, FailureKind::Refused),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+
+        // Logoff (exit code 2) after the session was up
+        let (s2, launches2) = supervise_script("sleep 0.5; exit 2", &[REFUSED], policy(5));
+        assert_eq!(next_event(&s2), Event::Connected);
+        assert_eq!(next_event(&s2), Event::Ended);
+        assert_eq!(launches.load(Ordering::SeqCst) + launches2.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn suspend_closes_the_client_and_resume_starts_another() {
+        let (s, launches) = supervise_script("exec sleep 30", &["exec sleep 30"], policy(2));
+        assert_eq!(next_event(&s), Event::Connected);
+        let first = s.client_pid();
+
+        s.suspend();
+        eventually("the client is closed", || !running(first));
+        std::thread::sleep(Duration::from_millis(500));
+        assert_eq!(launches.load(Ordering::SeqCst), 0, "nothing starts while suspended");
+        assert!(s.try_event().is_none(), "closing on purpose is not a drop");
+
+        s.resume();
+        assert_eq!(next_event(&s), Event::Connected);
+        assert_eq!(launches.load(Ordering::SeqCst), 1);
+        let second = s.client_pid();
+        assert_ne!(second, first);
+        assert!(running(second));
+
+        s.stop();
+        eventually("the resumed client is killed", || !running(second));
+    }
+
+    #[test]
+    fn stop_while_suspended_ends_the_thread_without_relaunching() {
+        let (s, launches) = supervise_script("exec sleep 30", &["exec sleep 30"], policy(2));
+        let first = s.client_pid();
+        s.suspend();
+        eventually("the client is closed", || !running(first));
+        s.stop();
+        s.resume();
+        assert_eq!(
+            s.events.recv_timeout(Duration::from_secs(10)),
+            Err(mpsc::RecvTimeoutError::Disconnected),
+            "the supervisor thread must end quietly"
+        );
+        assert_eq!(launches.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn suspend_during_backoff_waits_for_resume_without_relaunching() {
+        let slow = ReconnectPolicy { initial_delay: Duration::from_secs(1), max_delay: Duration::from_secs(1), ..policy(2) };
+        let (s, launches) = supervise_script(DROPPED, &["exec sleep 30"], slow);
+        assert_eq!(next_event(&s), Event::Connected);
+        assert!(matches!(next_event(&s), Event::Reconnecting { attempt: 1, .. }));
+        s.suspend();
+        std::thread::sleep(Duration::from_millis(1500));
+        assert_eq!(launches.load(Ordering::SeqCst), 0, "no reconnecting while suspended");
+
+        s.resume();
+        assert_eq!(next_event(&s), Event::Connected);
+        assert_eq!(launches.load(Ordering::SeqCst), 1);
+        s.stop();
+    }
+
+    #[test]
+    fn failing_to_restart_on_resume_reconnects_with_backoff() {
+        let output = OutputLog::new(freerdp::OUTPUT_LOG_LINES);
+        let launches = Arc::new(AtomicUsize::new(0));
+        let counter = launches.clone();
+        let relaunch_output = output.clone();
+        let launch = move || match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(io::Error::new(io::ErrorKind::NotFound, "sdl-freerdp3 not found")),
+            _ => client("exec sleep 30", &relaunch_output),
+        };
+        let first = client("exec sleep 30", &output).expect("spawn sh");
+        let s = Supervisor::start(first, launch, output, policy(2));
+        assert_eq!(next_event(&s), Event::Connected);
+
+        s.suspend();
+        std::thread::sleep(Duration::from_millis(300));
+        s.resume();
+        match next_event(&s) {
+            Event::Reconnecting { attempt: 1, reason, .. } => {
+                assert_eq!(reason.as_deref(), Some("Could not restart RDP client: sdl-freerdp3 not found"))
+            }
+            other => panic!("expected a reconnect, got {:?}", other),
+        }
+        assert_eq!(next_event(&s), Event::Connected);
+        assert_eq!(launches.load(Ordering::SeqCst), 2);
+        s.stop();
+    }
+}
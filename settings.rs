@@ -0,0 +1,247 @@
+//! Device behavior settings from `~/.config/mote/settings.toml`.
+//!
+//! Like the other config files this one is optional, and so is every key in
+//! it; missing values keep the built-in defaults:
+//!
+//! ```toml
+//! [idle]
+//! dim_after_secs = 60       # 0 = never dim
+//! dim_percent = 30
+//! off_after_secs = 120      # 0 = never blank
+//! disconnect_after_mins = 0 # 0 = stay connected
+//! disconnect_when_off_mins = 0  # close RDP while blank, reconnect on wake
+//! home_off_after_secs = 60  # home screen, nobody connected; 0 = never blank
+//! keep_awake_mins = 60      # "Keep awake" turns itself off; 0 = never
+//! wake = "tap"              # "input": any touch wakes and reaches the session
+//!
+//! [schedule]
+//! wake_on_touch_secs = 30   # touch lights the screen this long during off hours
+//!
+//! [[schedule.window]]
+//! from = "23:00"
+//! to = "07:00"
+//! screen_off = true
+//!
+//! [[schedule.window]]
+//! from = "19:00"
+//! to = "23:00"
+//! brightness = 30           # percent, at most
+//!
+//! [auto_brightness]
+//! enabled = false
+//! sensor = "iio:device0"    # first one with an illuminance channel if unset
+//! curve = [[0, 10], [20, 30], [100, 60], [400, 100]]  # [lux, percent]
+//! hysteresis_percent = 5
+//! smoothing = 0.2           # weight of each new reading, 0-1
+//!
+//! [screensaver]
+//! mode = "blank"            # "photos": slideshow, "clock": time and date
+//! photo_dir = "/home/m/Pictures"  # default ~/.config/mote/photos
+//! interval_secs = 15
+//! brightness = 40           # percent while the screensaver runs
+//! show_hosts = false        # clock: whether each profile's host answers
+//! ```
+
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::log;
+
+/// How a blank screen wakes up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WakeMode {
+    /// A tap on the wake overlay; the tap itself is swallowed.
+    #[default]
+    Tap,
+    /// Any input seen by `activity::InputMonitor`, passed through as usual.
+    /// Mote view only: the home screen still swallows its wake tap.
+    Input,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IdleSettings {
+    pub dim_after_secs: u64,
+    /// Backlight level while dimmed, in percent of `max_brightness`.
+    pub dim_percent: u32,
+    pub off_after_secs: u64,
+    /// Return to the home screen after this long idle in Mote view.
+    pub disconnect_after_mins: u64,
+    /// Close the RDP client once the screen has been off this long, and
+    /// connect again when it wakes (0 = keep streaming).
+    pub disconnect_when_off_mins: u64,
+    /// Blank the home screen (no Mote view up) after this long idle.
+    pub home_off_after_secs: u64,
+    /// How long the control overlay's "Keep awake" lasts.
+    pub keep_awake_mins: u64,
+    pub wake: WakeMode,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        IdleSettings {
+            dim_after_secs: 60,
+            dim_percent: 30,
+            off_after_secs: 120,
+            disconnect_after_mins: 0,
+            disconnect_when_off_mins: 0,
+            home_off_after_secs: 60,
+            keep_awake_mins: 60,
+            wake: WakeMode::Tap,
+        }
+    }
+}
+
+/// One `[[schedule.window]]`, times as "HH:MM" (see `schedule`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScheduleWindow {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub screen_off: bool,
+    #[serde(default)]
+    pub brightness: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    /// 0 = a wake tap during off hours only lights the screen until the
+    /// next check, about a second.
+    pub wake_on_touch_secs: u64,
+    #[serde(rename = "window")]
+    pub windows: Vec<ScheduleWindow>,
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        ScheduleSettings {
+            wake_on_touch_secs: 30,
+            windows: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AutoBrightnessSettings {
+    pub enabled: bool,
+    /// IIO device name, e.g. "iio:device0".
+    pub sensor: Option<String>,
+    /// `(lux, percent)` points, interpolated in between.
+    pub curve: Vec<(f64, u32)>,
+    /// Smallest change in percent worth writing.
+    pub hysteresis_percent: u32,
+    pub smoothing: f64,
+}
+
+impl Default for AutoBrightnessSettings {
+    fn default() -> Self {
+        AutoBrightnessSettings {
+            enabled: false,
+            sensor: None,
+            curve: vec![(0.0, 10), (20.0, 30), (100.0, 60), (400.0, 100)],
+            hysteresis_percent: 5,
+            smoothing: 0.2,
+        }
+    }
+}
+
+/// What idle shows once it reaches the screen-off stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreensaverMode {
+    #[default]
+    Blank,
+    Photos,
+    Clock,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScreensaverSettings {
+    pub mode: ScreensaverMode,
+    pub photo_dir: Option<PathBuf>,
+    pub interval_secs: u64,
+    pub brightness: u32,
+    pub show_hosts: bool,
+}
+
+impl Default for ScreensaverSettings {
+    fn default() -> Self {
+        ScreensaverSettings {
+            mode: ScreensaverMode::Blank,
+            photo_dir: None,
+            interval_secs: 15,
+            brightness: 40,
+            show_hosts: false,
+        }
+    }
+}
+
+impl ScreensaverSettings {
+    pub fn photo_dir(&self) -> PathBuf {
+        self.photo_dir.clone().unwrap_or_else(|| config::config_dir().join("photos"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub idle: IdleSettings,
+    pub schedule: ScheduleSettings,
+    pub auto_brightness: AutoBrightnessSettings,
+    pub screensaver: ScreensaverSettings,
+}
+
+pub fn parse_settings(text: &str) -> Result<Settings, String> {
+    toml::from_str(text).map_err(|e| e.to_string())
+}
+
+/// Load `settings.toml`. `SCREEN_OFF_DELAY` (seconds) still overrides
+/// `idle.off_after_secs` for existing deployments.
+pub fn load_settings() -> Settings {
+    let mut settings = load_settings_from(&config::config_dir().join("settings.toml"));
+    if let Some(delay) = env::var("SCREEN_OFF_DELAY").ok().and_then(|v| v.parse().ok()) {
+        settings.idle.off_after_secs = delay;
+    }
+    settings
+}
+
+pub fn load_settings_from(path: &Path) -> Settings {
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_settings(&text).unwrap_or_else(|e| {
+            log(&format!("Invalid {}: {} - using defaults", path.display(), e));
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_settings_keep_defaults() {
+        let settings = parse_settings("[idle]\ndim_percent = 10\ndisconnect_after_mins = 30\n").expect("parse");
+        assert_eq!(settings.idle.dim_percent, 10);
+        assert_eq!(settings.idle.disconnect_after_mins, 30);
+        assert_eq!(settings.idle.off_after_secs, 120, "unset keys must keep defaults");
+        assert_eq!(settings.idle.home_off_after_secs, 60);
+        assert_eq!(settings.idle.wake, WakeMode::Tap);
+        assert_eq!(parse_settings("[idle]\nwake = \"input\"").expect("parse").idle.wake, WakeMode::Input);
+        assert_eq!(parse_settings("").expect("empty file"), Settings::default());
+        assert!(parse_settings("[idle]\ndim_percent = \"low\"").is_err());
+
+        let auto = parse_settings("[auto_brightness]\nenabled = true\ncurve = [[0, 20], [250.5, 90]]\n").expect("parse");
+        assert_eq!(auto.auto_brightness.curve, [(0.0, 20), (250.5, 90)], "integer lux must parse too");
+        assert_eq!(auto.auto_brightness.hysteresis_percent, 5);
+        let saver = parse_settings("[screensaver]\nmode = \"photos\"\n").expect("parse");
+        assert_eq!(saver.screensaver.mode, ScreensaverMode::Photos);
+        assert!(parse_settings("[screensaver]\nmode = \"aquarium\"\n").is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! Minimal i3/Sway IPC client.
+//!
+//! Messages on the sway socket are `"i3-ipc"`, a u32 payload length, a u32
+//! message type (both native byte order) and a JSON payload. Replies carry
+//! the request's type; events the event type with the high bit set.
+//!
+//! `Connection` covers the requests Mote needs (RUN_COMMAND, GET_OUTPUTS,
+//! GET_TREE) with typed replies. `Connection::subscribe` turns a connection
+//! into a `Subscription` whose reader thread parses window and workspace
+//! events for the GTK main loop to poll with `Subscription::try_event`.
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use crate::log;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+const EVENT_BIT: u32 = 1 << 31;
+const EVENT_WORKSPACE: u32 = EVENT_BIT;
+const EVENT_WINDOW: u32 = EVENT_BIT | 3;
+/// Larger payloads mean we lost framing, not a real tree.
+const MAX_PAYLOAD: usize = 16 << 20;
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum Error {
+    /// No sway socket found (not running under sway).
+    NoSocket,
+    Io(io::Error),
+    /// Malformed message or unexpected reply.
+    Protocol(String),
+    /// sway rejected a command or subscription.
+    Command(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoSocket => write!(f, "No sway IPC socket found"),
+            Error::Io(e) => write!(f, "Sway IPC I/O error: {}", e),
+            Error::Protocol(msg) => write!(f, "Sway IPC protocol error: {}", msg),
+            Error::Command(msg) => write!(f, "Sway command failed: {}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// One entry of a RUN_COMMAND reply.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CommandOutcome {
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// GET_OUTPUTS entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Output {
+    pub name: String,
+    #[serde(default)]
+    pub active: bool,
+    /// Older sways report `dpms`, newer ones `power`.
+    #[serde(default, alias = "dpms")]
+    pub power: Option<bool>,
+    #[serde(default)]
+    pub current_workspace: Option<String>,
+}
+
+/// A node of the GET_TREE reply, also the container in window events.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default)]
+pub struct Node {
+    pub id: i64,
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    pub pid: Option<u32>,
+    pub app_id: Option<String>,
+    pub focused: bool,
+    pub nodes: Vec<Node>,
+    pub floating_nodes: Vec<Node>,
+}
+
+impl Node {
+    /// Depth-first search of this node and everything below it.
+    pub fn find(&self, pred: &dyn Fn(&Node) -> bool) -> Option<&Node> {
+        if pred(self) {
+            return Some(self);
+        }
+        self.nodes.iter().chain(&self.floating_nodes).find_map(|n| n.find(pred))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowChange {
+    New,
+    Close,
+    Focus,
+    Title,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceChange {
+    Init,
+    Empty,
+    Focus,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwayEvent {
+    Window { change: WindowChange, container: Node },
+    Workspace { change: WorkspaceChange, current: Option<Node> },
+}
+
+#[derive(Deserialize)]
+struct WindowPayload {
+    change: WindowChange,
+    container: Node,
+}
+
+#[derive(Deserialize)]
+struct WorkspacePayload {
+    change: WorkspaceChange,
+    #[serde(default)]
+    current: Option<Node>,
+}
+
+#[derive(Deserialize)]
+struct SubscribeReply {
+    success: bool,
+}
+
+/// Parse an event message; `None` for event types we did not ask for.
+pub fn parse_event(msg_type: u32, payload: &[u8]) -> Option<SwayEvent> {
+    match msg_type {
+        EVENT_WINDOW => {
+            let p: WindowPayload = serde_json::from_slice(payload).ok()?;
+            Some(SwayEvent::Window { change: p.change, container: p.container })
+        }
+        EVENT_WORKSPACE => {
+            let p: WorkspacePayload = serde_json::from_slice(payload).ok()?;
+            Some(SwayEvent::Workspace { change: p.change, current: p.current })
+        }
+        _ => None,
+    }
+}
+
+fn write_message<W: Write>(w: &mut W, msg_type: u32, payload: &[u8]) -> io::Result<()> {
+    let mut msg = Vec::with_capacity(14 + payload.len());
+    msg.extend_from_slice(MAGIC);
+    msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&msg_type.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    w.write_all(&msg)
+}
+
+fn read_message<R: Read>(r: &mut R) -> Result<(u32, Vec<u8>), Error> {
+    let mut header = [0u8; 14];
+    r.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(Error::Protocol("bad magic".to_string()));
+    }
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let msg_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+    if len > MAX_PAYLOAD {
+        return Err(Error::Protocol(format!("{} byte payload", len)));
+    }
+    let mut payload = vec![0u8; len];
+    r.read_exact(&mut payload)?;
+    Ok((msg_type, payload))
+}
+
+pub struct Connection {
+    stream: UnixStream,
+}
+
+impl Connection {
+    pub fn connect(socket: &Path) -> Result<Connection, Error> {
+        let stream = UnixStream::connect(socket)?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        Ok(Connection { stream })
+    }
+
+    fn request<T: DeserializeOwned>(&mut self, msg_type: u32, payload: &str) -> Result<T, Error> {
+        write_message(&mut self.stream, msg_type, payload.as_bytes())?;
+        let (reply_type, reply) = read_message(&mut self.stream)?;
+        if reply_type != msg_type {
+            return Err(Error::Protocol(format!("reply type {} to request {}", reply_type, msg_type)));
+        }
+        serde_json::from_slice(&reply).map_err(|e| Error::Protocol(format!("bad reply: {}", e)))
+    }
+
+    /// Run sway command(s); any failure becomes `Error::Command`.
+    pub fn run_command(&mut self, command: &str) -> Result<(), Error> {
+        let outcomes: Vec<CommandOutcome> = self.request(RUN_COMMAND, command)?;
+        let errors: Vec<String> = outcomes
+            .into_iter()
+            .filter(|o| !o.success)
+            .map(|o| o.error.unwrap_or_else(|| "unknown error".to_string()))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Command(format!("{}: {}", command, errors.join("; "))))
+        }
+    }
+
+    pub fn get_outputs(&mut self) -> Result<Vec<Output>, Error> {
+        self.request(GET_OUTPUTS, "")
+    }
+
+    pub fn get_tree(&mut self) -> Result<Node, Error> {
+        self.request(GET_TREE, "")
+    }
+
+    /// Subscribe to window and workspace events.
+    pub fn subscribe(mut self) -> Result<Subscription, Error> {
+        let reply: SubscribeReply = self.request(SUBSCRIBE, r#"["window","workspace"]"#)?;
+        if !reply.success {
+            return Err(Error::Command("subscribe refused".to_string()));
+        }
+        // Events arrive whenever sway has them
+        self.stream.set_read_timeout(None)?;
+        let mut reader = self.stream.try_clone()?;
+        let (tx, events) = mpsc::channel();
+        std::thread::spawn(move || {
+            loop {
+                match read_message(&mut reader) {
+                    Ok((msg_type, payload)) => {
+                        if let Some(event) = parse_event(msg_type, &payload) {
+                            if tx.send(event).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        log(&format!("Sway event subscription ended: {}", e));
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Subscription { stream: self.stream, events })
+    }
+}
+
+pub struct Subscription {
+    stream: UnixStream,
+    events: Receiver<SwayEvent>,
+}
+
+impl Subscription {
+    pub fn try_event(&self) -> Option<SwayEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // Unblocks the reader thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    /// Fake sway: answers each request with the next canned reply, then
+    /// (after a SUBSCRIBE) sends `events`.
+    fn fake_sway(
+        name: &str,
+        replies: Vec<(u32, &'static str)>,
+        events: Vec<(u32, &'static str)>,
+    ) -> (std::path::PathBuf, std::thread::JoinHandle<Vec<(u32, String)>>) {
+        let path = std::env::temp_dir().join(format!("mote-sway-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("bind fake sway socket");
+        let server = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().expect("accept");
+            let mut requests = Vec::new();
+            for (reply_type, reply) in replies {
+                let (msg_type, payload) = read_message(&mut conn).expect("read request");
+                requests.push((msg_type, String::from_utf8(payload).unwrap()));
+                write_message(&mut conn, reply_type, reply.as_bytes()).expect("write reply");
+            }
+            for (event_type, event) in events {
+                write_message(&mut conn, event_type, event.as_bytes()).expect("write event");
+            }
+            requests
+        });
+        (path, server)
+    }
+
+    #[test]
+    fn requests_and_typed_replies() {
+        let (path, server) = fake_sway(
+            "requests",
+            vec![
+                (RUN_COMMAND, r#"[{"success": true}]"#),
+                (RUN_COMMAND, r#"[{"success": false, "parse_error": true, "error": "Unknown command 'bogus'"}]"#),
+                (GET_OUTPUTS, r#"[{"name": "DSI-1", "active": true, "dpms": true, "current_workspace": "1"}]"#),
+                (
+                    GET_TREE,
+                    r#"{"id": 1, "type": "root", "nodes": [{"id": 3, "type": "output", "nodes": [
+                        {"id": 12, "type": "con", "pid": 4242, "app_id": "sdl-freerdp3", "nodes": []}]}]}"#,
+                ),
+            ],
+            vec![],
+        );
+        let mut conn = Connection::connect(&path).expect("connect");
+        conn.run_command("workspace 1").expect("workspace 1");
+        match conn.run_command("bogus") {
+            Err(Error::Command(msg)) => assert!(msg.contains("Unknown command"), "{}", msg),
+            other => panic!("expected command error, got {:?}", other),
+        }
+        let outputs = conn.get_outputs().expect("outputs");
+        assert_eq!(outputs[0].name, "DSI-1");
+        assert_eq!(outputs[0].power, Some(true));
+        let tree = conn.get_tree().expect("tree");
+        let rdp = tree.find(&|n| n.pid == Some(4242)).expect("window by pid");
+        assert_eq!(rdp.id, 12);
+        assert_eq!(rdp.app_id.as_deref(), Some("sdl-freerdp3"));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0], (RUN_COMMAND, "workspace 1".to_string()));
+        assert_eq!(requests[2].0, GET_OUTPUTS);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn subscription_delivers_window_and_workspace_events() {
+        let (path, server) = fake_sway(
+            "subscribe",
+            vec![(SUBSCRIBE, r#"{"success": true}"#)],
+            vec![
+                (EVENT_WINDOW, r#"{"change": "new", "container": {"id": 12, "type": "con", "pid": 4242, "app_id": "sdl-freerdp3"}}"#),
+                (EVENT_BIT | 4, r#"{"change": "reload"}"#),
+                (EVENT_WORKSPACE, r#"{"change": "focus", "old": {"name": "2"}, "current": {"id": 4, "name": "1"}}"#),
+                (EVENT_WINDOW, r#"{"change": "move", "container": {"id": 12}}"#),
+            ],
+        );
+        let sub = Connection::connect(&path).expect("connect").subscribe().expect("subscribe");
+        let requests = server.join().unwrap();
+        assert_eq!(requests, [(SUBSCRIBE, r#"["window","workspace"]"#.to_string())]);
+
+        let mut events = Vec::new();
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while events.len() < 3 && std::time::Instant::now() < deadline {
+            match sub.try_event() {
+                Some(e) => events.push(e),
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        match &events[..] {
+            [SwayEvent::Window { change: WindowChange::New, container }, SwayEvent::Workspace { change: WorkspaceChange::Focus, current: Some(ws) }, SwayEvent::Window { change: WindowChange::Other, .. }] =>
+            {
+                assert_eq!(container.pid, Some(4242));
+                assert_eq!(ws.name.as_deref(), Some("1"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}